@@ -3,150 +3,1360 @@
 
 extern crate doc_class;
 extern crate getopts;
-extern crate conllx;
+extern crate rust2vec;
 
-use getopts::Options;
-use std::path::Path;
+use getopts::{Options, Matches};
+use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
-use std::env::args;
+use std::env::{self, args};
 use std::process::exit;
-use std::hash::{Hash, Hasher};
-use std::collections::hash_map::DefaultHasher;
-use std::io::{BufReader, BufWriter, Write};
-use conllx::{Reader, Sentence};
+use std::io::{self, BufReader, BufWriter, BufRead, Write, stdout};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::thread;
+use rust2vec::Embeddings;
 use doc_class::io_utils::{open_file, create_file, iter_file_paths};
 use doc_class::numberer::{Numberer, HashMapNumberer};
-use doc_class::inverted_index::{InvertedIndex, binary, tf_idf, btf_idf, stf_idf};
-use doc_class::filters::{StopwordFilter, PTBStopwordFilter};
-
-// const OPEN_CLASS: [&'static str; 21] =
-//     ["SYM", "UH", "FW", "VB", "VBD", "VBG", "VBN", "VBP", "VBZ", "RB", "RBR",
-//      "RBS", "JJ", "JJR", "JJS", "NN", "NNS", "NNP", "NNPS", "NP", "NPS"];
+use doc_class::inverted_index::InvertedIndex;
+use doc_class::blocked_index::BlockedIndexBuilder;
+use doc_class::weighting::{Weighting, Feature, Norm};
+use doc_class::filters::{Stopwords, ListStopwordFilter};
+use doc_class::formats::{Format, write_features, write_multi_svm, write_svm, write_vw};
+use doc_class::sparse_vec::SparseVec;
+use doc_class::linear_model::{LinearModel, Solver, MAX_ITER};
+use doc_class::naive_bayes::{NaiveBayes, EventModel};
+use doc_class::vector_space::{NearestCentroid, KNearest};
+use doc_class::vectorizer::{Vectorizer, document_terms};
+use doc_class::corpus::{CorpusReader, InputFormat};
+use doc_class::hashing::{FeatureHasher, HashAlgorithm};
+use doc_class::extraction::{NgramExtractor, NgramKind, TokenUnit, parse_specs,
+                             read_clusters};
+use doc_class::evaluation::{ConfusionMatrix, MultiLabelEvaluation, Report, read_labels,
+                            read_label_sets, pair_labels};
+use doc_class::multi_label::{LabelManifest, OneVsRest};
+use doc_class::dictionary::{TermNames, write_feature_dict, read_feature_dict, top_weights};
+use doc_class::selection::{Criterion, select_terms};
+use doc_class::embedding::{DocEmbedder, Pooling, read_embeddings};
 
 fn main() {
-    let (path_in, path_out, n1, n2, n3, min_freq, feat_fn) = {
-        let mut opts = Options::new();
-        opts.reqopt("i", "input", "directory with grouped conll files.", "")
-            .optopt("o", "output", "filename for the output; default: `data.svm`.", "")
-            .optopt("1", "unigram", "dimensions for unigram hashing; default: `2^24`.", "")
-            .optopt("2", "bigram", "dimensions for bigram hashing; default: `0`.", "")
-            .optopt("3", "trigram", "dimensions for trigrams hashing; default: `0`.", "")
-            .optopt("t", "threshold", "the minimal ngram frequency; default: `1`.", "")
-            .optopt("f", "feature", "`binary`, `tfidf`, `btfidf`, or `stfidf` by default.", "");
-
-        let matches = match opts.parse(args().skip(1)) {
-            Err(e) => {
-                println!("{}", opts.usage(&e.to_string()));
+    let mut args = args().skip(1).collect::<Vec<_>>();
+    let mode = match args.first() {
+        Some(arg) if !arg.starts_with('-') => args.remove(0),
+        _ => "featurize".to_owned(),
+    };
+    match mode.as_ref() {
+        "featurize" => main_featurize(&args),
+        "train" => main_train(&args),
+        "transform" => main_transform(&args),
+        "predict" => main_predict(&args),
+        "cv" => main_cv(&args),
+        "eval" => main_eval(&args),
+        "top" => main_top(&args),
+        unk => {
+            println!("unknown mode: {}; try `featurize`, `train`, `transform`, `predict`, `cv`, \
+                      `eval`, or `top`.",
+                     unk);
+            exit(1)
+        }
+    }
+}
+
+/// writes the document features, in svmlight format by default.
+fn main_featurize(args: &[String]) {
+    let mut opts = Options::new();
+    dict_options(format_options(corpus_options(&mut opts)))
+        .optopt("o", "output", "filename for the output; default: `data.svm`.", "")
+        .optopt("M", "memory", "featurizes out of core, keeping about this many megabytes of \
+                                postings in memory, and the rest in sorted runs on disk; only \
+                                for the `svm` and `vw` formats, without `-S` and `-E`; \
+                                default: none.", "")
+        .optopt("", "tmp-dir", "the directory for the runs of `-M`; default: the temporary \
+                                directory of the system.", "");
+    let matches = parse_args(&opts, args);
+    let corpus = CorpusConfig::from_matches(&matches);
+    let path_out = matches.opt_str("o").unwrap_or("data.svm".to_owned());
+
+    let format = format_from_matches(&matches);
+    if let Some(memory) = matches.opt_str("M") {
+        let budget = match memory.parse::<usize>() {
+            Ok(megabytes) if 0 < megabytes => megabytes << 20,
+            _ => {
+                println!("bad memory budget: {}", memory);
                 exit(1)
             }
-            Ok(m) => m,
         };
+        featurize_blocked(&matches, &corpus, budget, format, &path_out);
+        return;
+    }
+
+    let mut names = TermNames::new();
+    let docs = corpus.read(names_if_needed(&matches, &mut names));
+    let (labels, features, vectorizer, _) = corpus.fit(&docs);
+    save_vectorizer(&matches, &vectorizer);
+    save_dicts(&matches, &vectorizer, &names);
+    if corpus.multi_label {
+        let label_sets = number_label_sets(&mut class_numberer(&vectorizer), &docs);
+        write_multi_features(format, &path_out, &label_sets, &features);
+    } else {
+        write_features(format, &path_out, &labels, &features, vectorizer.dim()).unwrap();
+    }
+}
+
+/// the number of documents read by each thread at a time in
+/// `featurize_blocked`.
+const DOCS_PER_THREAD: usize = 64;
+
+/// featurizes the corpus as `main_featurize`, but through a `BlockedIndex`,
+/// reading the documents in batches, so that neither the documents nor their
+/// postings are all kept in memory, and writing the feature vectors as they
+/// are merged.
+fn featurize_blocked(matches: &Matches,
+                     corpus: &CorpusConfig,
+                     budget: usize,
+                     format: Format,
+                     path_out: &str) {
+    if corpus.selection.is_some() || corpus.embeddings.is_some() {
+        println!("`-M` works without `-S` and `-E`.");
+        exit(1)
+    }
+    if format != Format::Svm && (format != Format::Vw || corpus.multi_label) {
+        println!("`-M` only writes the svm format, or the vw format without label sets.");
+        exit(1)
+    }
+    let tmp_dir = matches.opt_str("tmp-dir").map_or_else(env::temp_dir, PathBuf::from);
+
+    corpus.print_settings();
+    let files = corpus_files(&corpus.path_in, corpus.manifest.as_ref());
+    let mut classes = HashMapNumberer::new();
+    let mut labels = Vec::new();
+    let mut label_sets = Vec::new();
+    let mut names = TermNames::new();
+    let mut builder = BlockedIndexBuilder::new(tmp_dir, budget);
+    for files in files.chunks(DOCS_PER_THREAD * corpus.threads) {
+        for doc in read_documents(files, labels.len(), &corpus.reading(false),
+                                  names_if_needed(matches, &mut names), corpus.threads) {
+            // the first label is the class, so it is numbered first either way.
+            labels.push(classes.number(&doc.class));
+            if corpus.multi_label {
+                label_sets.push(doc.labels.iter().map(|class| classes.number(class)).collect());
+            }
+            builder.push_signed(doc.terms.into_iter()).unwrap();
+        }
+    }
+    let index = builder.finish().unwrap();
+    println!("runs: {}", index.run_count());
+    let stats = index.term_stats(&labels, classes.len(), corpus.min_freq,
+                                 corpus.feature.needs_classes())
+        .unwrap();
+    println!("actual total dim: {}", stats.terms.len());
+    let rows = index.doc_features(&stats, &corpus.feature, &labels, corpus.norm).unwrap();
+    let vectorizer = Vectorizer::from_stats(stats, &labels, corpus.stopwords.clone(),
+                                            corpus.extractor.clone(), corpus.feature,
+                                            classes.strings().to_vec())
+        .with_norm(corpus.norm);
+    save_vectorizer(matches, &vectorizer);
+    save_dicts(matches, &vectorizer, &names);
+
+    let mut wtr = BufWriter::new(create_file(path_out));
+    for (doc, res_feat_vec) in rows.enumerate() {
+        let features = [res_feat_vec.unwrap()];
+        if corpus.multi_label {
+            write_multi_svm(&mut wtr, &label_sets[doc..doc + 1], &features).unwrap();
+        } else if format == Format::Vw {
+            write_vw(&mut wtr, &labels[doc..doc + 1], &features).unwrap();
+        } else {
+            write_svm(&mut wtr, &labels[doc..doc + 1], &features).unwrap();
+        }
+    }
+}
+
+/// fits a classifier on the documents and saves the model.
+fn main_train(args: &[String]) {
+    let mut opts = Options::new();
+    dict_options(train_options(corpus_options(&mut opts)))
+        .optopt("m", "model", "filename for the model; default: `data.model`.", "");
+    let matches = parse_args(&opts, args);
+    let corpus = CorpusConfig::from_matches(&matches);
+    let trainer = TrainConfig::from_matches(&matches);
+    let path_model = matches.opt_str("m").unwrap_or("data.model".to_owned());
+
+    let mut names = TermNames::new();
+    let docs = corpus.read(names_if_needed(&matches, &mut names));
+    let (labels, features, vectorizer, inv_idx) = corpus.fit(&docs);
+    save_vectorizer(&matches, &vectorizer);
+    save_dicts(&matches, &vectorizer, &names);
 
-        (matches.opt_str("i").unwrap(),
-         matches.opt_str("o").unwrap_or("data.svm".to_owned()),
-         matches.opt_str("1").unwrap_or_default().parse::<usize>().unwrap_or(usize::pow(2, 24)),
-         matches.opt_str("2").unwrap_or_default().parse::<usize>().unwrap_or(0),
-         matches.opt_str("3").unwrap_or_default().parse::<usize>().unwrap_or(0),
-         matches.opt_str("t").unwrap_or_default().parse::<usize>().unwrap_or(1),
-         match matches.opt_str("f").unwrap_or("stfidf".to_owned()).as_ref() {
-             "binary" => binary,
-             "tfidf" => tf_idf,
-             "btfidf" => btf_idf,
-             "stfidf" => stf_idf,
-             unk => {
-                 println!("unknown feature: {}", unk);
-                 exit(1)
-             }
-         })
+    let model = if corpus.multi_label {
+        let label_sets = number_label_sets(&mut class_numberer(&vectorizer), &docs);
+        trainer.train_multi(&features, &label_sets, vectorizer.classes().len())
+    } else {
+        trainer.train(&inv_idx, &features, &labels, corpus.min_freq)
     };
+    model.save(&mut BufWriter::new(create_file(path_model))).unwrap();
+}
 
-    println!("unigram dim: {}", n1);
-    println!("bigram  dim: {}", n2);
-    println!("trigram dim: {}", n3);
+/// estimates the accuracy by cross-validation, where the vectorizer of each
+/// fold sees only its training part, so that no statistics from the held-out
+/// part leak into the features.
+fn main_cv(args: &[String]) {
+    let mut opts = Options::new();
+    evaluation_options(train_options(corpus_options(&mut opts)))
+        .optopt("k", "folds", "the number of folds; default: `10`.", "")
+        .optmulti("x", "split",
+                  "a file listing the held-out documents for one fold, by path; \
+                   may be repeated, replacing `-k`.", "");
+    let matches = parse_args(&opts, args);
+    let corpus = CorpusConfig::from_matches(&matches);
+    let trainer = TrainConfig::from_matches(&matches);
+    let folds = match matches.opt_str("k") {
+        None => 10,
+        Some(folds) => {
+            match folds.parse::<usize>() {
+                Ok(folds) if 2 <= folds => folds,
+                _ => {
+                    println!("{}", opts.usage(&format!("bad number of folds: {}; at least `2`.",
+                                                       folds)));
+                    exit(1)
+                }
+            }
+        }
+    };
+    let splits = matches.opt_strs("x");
 
-    let (labels, features) = {
-        let file_paths = iter_file_paths(path_in);
-        let mut classes = HashMapNumberer::new();
-        let mut labels = Vec::new();
-        let mut inv_idx = InvertedIndex::new();
+    let docs = corpus.read(None);
+    // the held-out documents in each fold, either listed in the split files,
+    // or taken at every `folds`-th position.
+    let held_out = if splits.is_empty() {
+        (0..folds)
+            .map(|fold| docs.iter().map(|doc| doc.index % folds == fold).collect())
+            .collect::<Vec<Vec<bool>>>()
+    } else {
+        splits.iter()
+            .map(|split| {
+                let paths = match BufReader::new(open_file(split))
+                    .lines()
+                    .collect::<io::Result<Vec<_>>>() {
+                    Ok(lines) => {
+                        lines.iter()
+                            .map(|line| PathBuf::from(line.trim()))
+                            .collect::<HashSet<_>>()
+                    }
+                    Err(err) => {
+                        println!("cannot read split {}: {}", split, err);
+                        exit(1)
+                    }
+                };
+                docs.iter()
+                    .map(|doc| {
+                        paths.contains(&doc.path) ||
+                        doc.path.strip_prefix(&corpus.path_in).is_ok_and(|p| paths.contains(p))
+                    })
+                    .collect()
+            })
+            .collect()
+    };
 
-        // use std::collections::HashSet;
-        // let open_class = OPEN_CLASS.iter().map(ToOwned::to_owned).collect::<HashSet<_>>();
+    if corpus.multi_label {
+        return cv_multi(&matches, &corpus, &trainer, &docs, &held_out);
+    }
+    let mut confusion = ConfusionMatrix::new();
+    for (fold, is_held_out) in held_out.iter().enumerate() {
+        let (labels, features, vectorizer, inv_idx) =
+            corpus.fit(docs.iter().zip(is_held_out).filter(|&(_, &h)| !h).map(|(doc, _)| doc));
+        let model = trainer.train(&inv_idx, &features, &labels, corpus.min_freq);
+        let mut fold_confusion = ConfusionMatrix::new();
+        for doc in docs.iter().zip(is_held_out).filter(|&(_, &h)| h).map(|(doc, _)| doc) {
+            let label = model.predict(&vectorizer, doc)[0];
+            fold_confusion.add(&doc.class, &vectorizer.classes()[label]);
+            confusion.add(&doc.class, &vectorizer.classes()[label]);
+        }
+        println!("fold {} accuracy = {}% ({}/{})", fold + 1,
+                 100.0 * fold_confusion.accuracy(), fold_confusion.correct(), fold_confusion.total());
+    }
+    confusion.write_report(&mut stdout()).unwrap();
+    write_evaluation(&matches, &confusion);
+    println!("Cross Validation Accuracy = {}%", 100.0 * confusion.accuracy());
+}
 
-        let filter = PTBStopwordFilter;
+/// cross-validates one-vs-rest models on documents with label sets.
+fn cv_multi(matches: &Matches,
+            corpus: &CorpusConfig,
+            trainer: &TrainConfig,
+            docs: &[Document],
+            held_out: &[Vec<bool>]) {
+    let mut evaluation = MultiLabelEvaluation::new();
+    for (fold, is_held_out) in held_out.iter().enumerate() {
+        let train_docs = docs.iter()
+            .zip(is_held_out)
+            .filter(|&(_, &h)| !h)
+            .map(|(doc, _)| doc)
+            .collect::<Vec<_>>();
+        let (_, features, vectorizer, _) = corpus.fit(train_docs.iter().cloned());
+        let label_sets = number_label_sets(&mut class_numberer(&vectorizer), train_docs);
+        let model = trainer.train_multi(&features, &label_sets, vectorizer.classes().len());
+        let mut fold_evaluation = MultiLabelEvaluation::new();
+        for doc in docs.iter().zip(is_held_out).filter(|&(_, &h)| h).map(|(doc, _)| doc) {
+            let pred = model.predict(&vectorizer, doc)
+                .into_iter()
+                .map(|label| vectorizer.classes()[label].as_str())
+                .collect::<Vec<_>>();
+            let gold = doc.labels.iter().map(String::as_str).collect::<Vec<_>>();
+            fold_evaluation.add(&gold, &pred);
+            evaluation.add(&gold, &pred);
+        }
+        println!("fold {} subset accuracy = {}% ({}/{})", fold + 1,
+                 100.0 * fold_evaluation.subset_accuracy(), fold_evaluation.exact_matches(),
+                 fold_evaluation.total());
+    }
+    evaluation.write_report(&mut stdout()).unwrap();
+    write_evaluation(matches, &evaluation);
+    println!("Cross Validation Subset Accuracy = {}%", 100.0 * evaluation.subset_accuracy());
+}
 
-        for file_path in file_paths {
-            match file_path.parent()
-                .and_then(Path::file_name)
-                .and_then(OsStr::to_str) {
-                    None => continue,
-                    Some(label) => labels.push(classes.number(label)),
+/// scores predicted labels against gold labels.
+fn main_eval(args: &[String]) {
+    let mut opts = Options::new();
+    evaluation_options(&mut opts)
+        .reqopt("g", "gold", "file with the gold labels, one per line, after the path of the \
+                              document and a tab if given.", "")
+        .reqopt("p", "pred", "file with the predicted labels, as for `-g`; paired by path when \
+                              both files have them, or else line by line.", "")
+        .optflag("M", "multi-label", "the labels are comma-separated sets, scored by \
+                                      example-based and label-based metrics.");
+    let matches = parse_args(&opts, args);
+    let (path_gold, path_pred) = (matches.opt_str("g").unwrap(), matches.opt_str("p").unwrap());
+    if matches.opt_present("M") {
+        let (gold, pred) = pair_or_exit(read_label_sets(BufReader::new(open_file(path_gold))),
+                                        read_label_sets(BufReader::new(open_file(path_pred))));
+        let evaluation = MultiLabelEvaluation::from_label_sets(&gold, &pred);
+        evaluation.write_report(&mut stdout()).unwrap();
+        write_evaluation(&matches, &evaluation);
+        return;
+    }
+    let (gold, pred) = pair_or_exit(read_labels(BufReader::new(open_file(path_gold))),
+                                    read_labels(BufReader::new(open_file(path_pred))));
+    let confusion = ConfusionMatrix::from_labels(&gold, &pred);
+    confusion.write_report(&mut stdout()).unwrap();
+    write_evaluation(&matches, &confusion);
+}
+
+/// pairs the gold and predicted labels as read, or exits.
+fn pair_or_exit<T>(gold: io::Result<Vec<(Option<String>, T)>>,
+                   pred: io::Result<Vec<(Option<String>, T)>>)
+                   -> (Vec<T>, Vec<T>) {
+    let (gold, pred) = match (gold, pred) {
+        (Ok(gold), Ok(pred)) => (gold, pred),
+        (Err(err), _) | (_, Err(err)) => {
+            println!("cannot read labels: {}", err);
+            exit(1)
+        }
+    };
+    match pair_labels(gold, pred) {
+        Ok(pairs) => pairs,
+        Err(err) => {
+            println!("cannot pair the labels: {}", err);
+            exit(1)
+        }
+    }
+}
+
+/// featurizes unseen documents with a saved vectorizer, in svmlight format by
+/// default.
+fn main_transform(args: &[String]) {
+    let mut opts = Options::new();
+    input_options(format_options(&mut opts))
+        .reqopt("i", "input", "directory with grouped corpus files.", "")
+        .reqopt("v", "vectorizer", "the saved vectorizer.", "")
+        .optopt("L", "labels", "a label manifest as for `featurize`, for writing label sets; \
+                                default: none.", "")
+        .optflag("", "multi-label", "writes the label sets given in the files, as `-L` does.")
+        .optopt("o", "output", "filename for the output; default: `data.svm`.", "")
+        .optopt("j", "threads", "the number of threads for reading; default: the number of cpus.",
+                "");
+    let matches = parse_args(&opts, args);
+    let vectorizer = load_vectorizer(&matches.opt_str("v").unwrap());
+    let manifest = matches.opt_str("L").map(|path| read_manifest(&path));
+    let path_out = matches.opt_str("o").unwrap_or("data.svm".to_owned());
+    let format = format_from_matches(&matches);
+
+    let mut classes = class_numberer(&vectorizer);
+    let reading = ReadConfig::from_vectorizer(input_from_matches(&matches), &vectorizer);
+    let docs = read_corpus(&matches.opt_str("i").unwrap(), manifest.as_ref(), &reading, None,
+                           threads_from_matches(&matches));
+    let features = docs.iter()
+        .map(|doc| vectorizer.transform_terms(doc.terms.iter().cloned(), &doc.tokens))
+        .collect::<Vec<_>>();
+    if manifest.is_some() || matches.opt_present("multi-label") {
+        let label_sets = number_label_sets(&mut classes, &docs);
+        write_multi_features(format, &path_out, &label_sets, &features);
+    } else {
+        let labels = docs.iter().map(|doc| classes.number(&doc.class)).collect::<Vec<_>>();
+        write_features(format, &path_out, &labels, &features, vectorizer.dim()).unwrap();
+    }
+}
+
+/// classifies unseen documents with a saved vectorizer and model, writing the
+/// path and the predicted class for each document, or the comma-separated
+/// classes for a one-vs-rest model.
+fn main_predict(args: &[String]) {
+    let mut opts = Options::new();
+    input_options(&mut opts)
+        .reqopt("i", "input", "directory with corpus files.", "")
+        .reqopt("v", "vectorizer", "the saved vectorizer.", "")
+        .reqopt("m", "model", "the saved model.", "")
+        .optopt("o", "output", "filename for the predictions; default: `data.pred`.", "")
+        .optopt("g", "gold", "filename for also writing the gold classes; default: none.", "")
+        .optopt("L", "labels", "a label manifest as for `featurize`, for the gold classes; \
+                                default: none.", "")
+        .optopt("j", "threads", "the number of threads for reading; default: the number of cpus.",
+                "");
+    let matches = parse_args(&opts, args);
+    let vectorizer = load_vectorizer(&matches.opt_str("v").unwrap());
+    let model = Model::load(&matches.opt_str("m").unwrap());
+    let path_out = matches.opt_str("o").unwrap_or("data.pred".to_owned());
+    let manifest = matches.opt_str("L").map(|path| read_manifest(&path));
+
+    let mut wtr = BufWriter::new(create_file(path_out));
+    let mut gold_wtr = matches.opt_str("g").map(|path| BufWriter::new(create_file(path)));
+    let reading = ReadConfig::from_vectorizer(input_from_matches(&matches), &vectorizer);
+    for doc in read_corpus(&matches.opt_str("i").unwrap(), manifest.as_ref(), &reading, None,
+                           threads_from_matches(&matches)) {
+        if let Some(ref mut gold_wtr) = gold_wtr {
+            writeln!(gold_wtr, "{}\t{}", doc.path.display(), doc.labels.join(",")).unwrap();
+        }
+        let pred = model.predict(&vectorizer, &doc)
+            .into_iter()
+            .map(|label| vectorizer.classes()[label].as_str())
+            .collect::<Vec<_>>();
+        writeln!(wtr, "{}\t{}", doc.path.display(), pred.join(",")).unwrap();
+    }
+}
+
+fn read_manifest(path: &str) -> LabelManifest {
+    match LabelManifest::read(BufReader::new(open_file(path))) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            println!("cannot read labels: {}", err);
+            exit(1)
+        }
+    }
+}
+
+/// numbers classes as the vectorizer does, with unseen classes after the known
+/// ones.
+fn class_numberer(vectorizer: &Vectorizer) -> HashMapNumberer {
+    HashMapNumberer::from_strings(vectorizer.classes())
+}
+
+fn number_label_sets<'a, I>(classes: &mut HashMapNumberer, docs: I) -> Vec<Vec<usize>>
+    where I: IntoIterator<Item = &'a Document>
+{
+    docs.into_iter()
+        .map(|doc| doc.labels.iter().map(|label| classes.number(label)).collect())
+        .collect()
+}
+
+/// writes label sets with `features`, which only the svmlight format allows.
+fn write_multi_features(format: Format,
+                        path_out: &str,
+                        label_sets: &[Vec<usize>],
+                        features: &[SparseVec<f32>]) {
+    if format != Format::Svm {
+        println!("label sets can only be written in the svm format.");
+        exit(1)
+    }
+    write_multi_svm(&mut BufWriter::new(create_file(path_out)), label_sets, features).unwrap();
+}
+
+/// prints the ngrams with the largest weights for each class of a linear
+/// model, given the dictionaries written along with it.
+fn main_top(args: &[String]) {
+    let mut opts = Options::new();
+    opts.reqopt("m", "model", "the saved linear or one-vs-rest model.", "")
+        .reqopt("l", "label-dict", "the classes by label number, from `--label-dict`.", "")
+        .reqopt("d", "feature-dict", "the ngrams by dimension, from `--feature-dict`.", "")
+        .optopt("k", "top", "the number of ngrams for each class; default: `20`.", "");
+    let matches = parse_args(&opts, args);
+    let k = match matches.opt_str("k") {
+        Some(k) => {
+            match k.parse::<usize>() {
+                Ok(k) => k,
+                Err(_) => {
+                    println!("bad number of ngrams: {}", k);
+                    exit(1)
                 }
+            }
+        }
+        None => 20,
+    };
+    let classes =
+        match HashMapNumberer::load(BufReader::new(open_file(matches.opt_str("l").unwrap()))) {
+            Ok(classes) => classes.strings().to_vec(),
+            Err(err) => {
+                println!("cannot load label dict: {}", err);
+                exit(1)
+            }
+        };
+    let dim2names =
+        match read_feature_dict(BufReader::new(open_file(matches.opt_str("d").unwrap()))) {
+            Ok(dim2names) => dim2names,
+            Err(err) => {
+                println!("cannot load feature dict: {}", err);
+                exit(1)
+            }
+        };
+    let mut class_weights = match Model::load(&matches.opt_str("m").unwrap()) {
+        Model::Linear(model) => model.class_weights(),
+        Model::OneVsRest(model) => model.class_weights(),
+        _ => {
+            println!("only linear models have weights for ngrams.");
+            exit(1)
+        }
+    };
+    class_weights.sort_by_key(|&(label, _)| label);
+    for (label, weights) in class_weights {
+        println!("{}", classes.get(label).map_or("?", String::as_str));
+        for (dim, weight) in top_weights(&weights, k) {
+            let names = dim2names.get(dim).map_or(String::new(), |names| names.join(" | "));
+            println!("{:>10.4} {:>8} {}", weight, dim, names);
+        }
+    }
+}
 
-            let tokens = Reader::new(BufReader::new(open_file(&file_path)))
-                .sentences()
-                .flat_map(|res_sent| res_sent.unwrap_or(Sentence::new(Vec::new())))
-            // .map(|tok| tok.form().unwrap_or("").to_owned())
-            // .filter_map(|tok| tok.pos().and_then(
-            //     |pos| if open_class.contains(pos) {
-            //         tok.form().map(ToOwned::to_owned)
-            //     } else {
-            //         None
-            //     }
-            // ))
-                .filter_map(|tok| {
-                    match (tok.form(), tok.pos(), tok.lemma()) {
-                        (Some(form), Some(pos), Some(lemma)) => {
-                            if filter.is_stopword(pos, lemma) {
-                                None
-                            } else {
-                                Some(form.to_owned())
+fn parse_args(opts: &Options, args: &[String]) -> Matches {
+    match opts.parse(args) {
+        Err(e) => {
+            println!("{}", opts.usage(&e.to_string()));
+            exit(1)
+        }
+        Ok(m) => m,
+    }
+}
+
+/// options for the format of the feature output.
+fn format_options(opts: &mut Options) -> &mut Options {
+    opts.optopt("F", "format", "`vw` for vowpal wabbit, `mtx` for matrix market, `npy` for csr \
+                                arrays in numpy files, `bin` for compact binary, or `svm` by \
+                                default.", "")
+}
+
+fn format_from_matches(matches: &Matches) -> Format {
+    let name = matches.opt_str("F").unwrap_or("svm".to_owned());
+    match Format::from_name(&name) {
+        Some(format) => format,
+        None => {
+            println!("unknown format: {}", name);
+            exit(1)
+        }
+    }
+}
+
+/// options for writing the numbers of the classes and the names of the
+/// dimensions.
+fn dict_options(opts: &mut Options) -> &mut Options {
+    opts.optopt("", "label-dict", "filename for writing the class of each label number, like \
+                                   `labels.tsv`; default: none.", "")
+        .optopt("", "feature-dict", "filename for writing the ngrams hashed into each dimension, \
+                                     like `features.tsv`, which keeps all ngram strings in \
+                                     memory; default: none.", "")
+}
+
+/// returns `names` for collecting the ngram names, when asked to write them.
+fn names_if_needed<'a>(matches: &Matches, names: &'a mut TermNames) -> Option<&'a mut TermNames> {
+    if matches.opt_present("feature-dict") {
+        Some(names)
+    } else {
+        None
+    }
+}
+
+fn save_dicts(matches: &Matches, vectorizer: &Vectorizer, names: &TermNames) {
+    if let Some(path) = matches.opt_str("label-dict") {
+        class_numberer(vectorizer).save(&mut BufWriter::new(create_file(path))).unwrap();
+    }
+    if let Some(path) = matches.opt_str("feature-dict") {
+        write_feature_dict(&mut BufWriter::new(create_file(path)), vectorizer, names).unwrap();
+    }
+}
+
+fn save_vectorizer(matches: &Matches, vectorizer: &Vectorizer) {
+    if let Some(path) = matches.opt_str("v") {
+        vectorizer.save(&mut BufWriter::new(create_file(path))).unwrap();
+    }
+}
+
+fn load_vectorizer(path: &str) -> Vectorizer {
+    match Vectorizer::load(BufReader::new(open_file(path))) {
+        Err(err) => {
+            println!("cannot load vectorizer: {}", err);
+            exit(1)
+        }
+        Ok(vectorizer) => vectorizer,
+    }
+}
+
+/// options for reading and featurizing a corpus, shared by all modes which fit
+/// a vectorizer.
+fn corpus_options(opts: &mut Options) -> &mut Options {
+    input_options(opts)
+        .reqopt("i", "input", "directory with grouped corpus files.", "")
+        .optopt("L", "labels", "a label manifest with lines of a document path, absolute or \
+                                relative to `-i`, and its comma-separated labels separated by \
+                                tab, replacing the directory names; unlisted documents are \
+                                skipped, and models are trained one-vs-rest; default: none.", "")
+        .optflag("", "multi-label", "trains one-vs-rest on the label sets given in the files, as \
+                                     for json lines, like with `-L`.")
+        .optopt("v", "vectorizer", "filename for saving the vectorizer; default: none.", "")
+        .optopt("w", "stopwords", "drops the closed classes and auxiliaries with `stts` tags, \
+                                   keeps all tokens with `none`, or uses `ptb` tags by default; \
+                                   untagged tokens are only dropped by `--stop-lemmas`.",
+                "")
+        .optopt("", "stop-tags", "a file with tags to drop, one per line, matching by prefix \
+                                  when ending in `*`; replaces `-w`.", "")
+        .optopt("", "stop-lemmas", "a file with lemmas to drop, one per line; replaces `-w`.", "")
+        .optopt("u", "unit", "what the ngrams are made of: `lower` for lowercased forms, `lemma`, \
+                              `lemma/pos`, `pos`, or `form` by default.", "")
+        .optopt("n", "ngrams", "comma-separated ngram orders, each hashed into its own dimensions, \
+                                like `1:2^24,2:2^25,s2:2^22,c3-5:2^22`: `N` for contiguous ngrams, \
+                                `sN` for 2-skip-ngrams, `KsN` for K-skip-ngrams, `cN` for \
+                                characters within words, `xN` for characters across words, \
+                                `kN` for word clusters from `-C`, where `N` may be a range like \
+                                `3-5`; `d` for head-dependent lemma pairs, `l` for labelled \
+                                arcs, `p` for paths of two arcs; default: `1:2^24`.", "")
+        .optopt("C", "clusters", "word clusters for `kN` ngrams, as lines of word and cluster \
+                                  separated by tab, like `kmeans` writes; default: none.", "")
+        .optopt("H", "hash", "the hash function, `fnv` or `murmur3` by default.", "")
+        .optopt("", "seed", "the seed for hashing; default: `0`.", "")
+        .optflag("", "signed", "gives each hashed ngram a sign, so that collisions cancel out.")
+        .optopt("t", "threshold", "the minimal ngram frequency; default: `1`.", "")
+        .optopt("f", "feature", "`binary`, `tfidf`, `btfidf`, `bm25[:K1[:B]]`, `pivoted[:SLOPE]`, \
+                                 `probidf`, `deltatfidf`, or `stfidf` by default.", "")
+        .optopt("N", "norm", "scaling of the feature vectors, `l1`, `none`, or `l2` by default.", "")
+        .optopt("S", "select", "score ngrams against the classes by `chi2`, `ig`, `pmi`, or `dfr`, \
+                                and keep only the best; default: none.", "")
+        .optopt("K", "top", "the number of ngrams kept by `-S`; default: `100000`.", "")
+        .optflag("", "per-class", "keeps the best ngrams for each class, instead of overall.")
+        .optopt("E", "embeddings", "word2vec binary file for appending pooled word vectors of the \
+                                    tokens to the features; default: none.", "")
+        .optopt("", "pooling", "pooling for `-E`: `tfidf` for the mean weighted by tf-idf, `max`, \
+                                or `mean` by default.", "")
+        .optopt("j", "threads", "the number of threads for reading and indexing the corpus; \
+                                 default: the number of cpus.", "")
+}
+
+/// options for the format of the corpus files.
+fn input_options(opts: &mut Options) -> &mut Options {
+    opts.optopt("I", "input-format", "`conllu` with universal tags, `conllu-xpos`, `text` for \
+                                      utf-8 text with a sentence per line, `jsonl` for json \
+                                      lines with a document per line, or `conllx` by default.",
+                "")
+        .optflag("", "lowercase", "lowercases the tokens of `text` and `jsonl`.")
+}
+
+fn input_from_matches(matches: &Matches) -> InputFormat {
+    let name = matches.opt_str("I").unwrap_or("conllx".to_owned());
+    match InputFormat::from_name(&name) {
+        Some(input) => input.with_lowercase(matches.opt_present("lowercase")),
+        None => {
+            println!("unknown input format: {}", name);
+            exit(1)
+        }
+    }
+}
+
+/// the number of threads from `-j`, or the available parallelism.
+fn threads_from_matches(matches: &Matches) -> usize {
+    match matches.opt_str("j") {
+        Some(threads) => {
+            match threads.parse::<usize>() {
+                Ok(threads) if 0 < threads => threads,
+                _ => {
+                    println!("bad number of threads: {}", threads);
+                    exit(1)
+                }
+            }
+        }
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    }
+}
+
+/// options for saving the evaluation scores.
+fn evaluation_options(opts: &mut Options) -> &mut Options {
+    opts.optopt("r", "report", "filename for also saving the scores; default: none.", "")
+        .optopt("", "report-format", "`json`, or `tsv` by default.", "")
+}
+
+fn write_evaluation<E>(matches: &Matches, evaluation: &E)
+    where E: Report
+{
+    if let Some(path) = matches.opt_str("r") {
+        let mut wtr = BufWriter::new(create_file(path));
+        match matches.opt_str("report-format").unwrap_or("tsv".to_owned()).as_ref() {
+            "tsv" => evaluation.write_tsv(&mut wtr).unwrap(),
+            "json" => evaluation.write_json(&mut wtr).unwrap(),
+            unk => {
+                println!("unknown report format: {}", unk);
+                exit(1)
+            }
+        }
+    }
+}
+
+/// options for training a classifier.
+fn train_options(opts: &mut Options) -> &mut Options {
+    opts.optopt("s", "solver", "`logreg`, `l1svm`, `mnb` or `bnb` for multinomial or bernoulli \
+                                naive bayes on the ngram counts, `rocchio` for the nearest \
+                                centroid, `knn` for the nearest neighbours, or `l2svm` by \
+                                default.", "")
+        .optopt("a", "alpha", "the additive smoothing for naive bayes, `1` for laplace; \
+                               default: `1`.", "")
+        .optopt("", "neighbors", "the number of neighbours for `knn`; default: `10`.", "")
+        .optopt("c", "cost", "the penalty for the loss; default: `1`.", "")
+        .optopt("B", "bias", "value of the bias feature, none if negative; default: `1`.", "")
+        .optopt("e", "epsilon", "tolerance for stopping; default: `0.1`.", "")
+}
+
+/// either a linear solver or a vector space classifier on the document
+/// features, or naive bayes on the counts in the inverted index.
+#[derive(Debug,Clone,Copy)]
+enum Learner {
+    Linear(Solver),
+    Bayes(EventModel),
+    Centroid,
+    Knn(usize),
+}
+
+struct TrainConfig {
+    learner: Learner,
+    alpha: f32,
+    cost: f32,
+    bias: f32,
+    eps: f32,
+}
+
+impl TrainConfig {
+    fn from_matches(matches: &Matches) -> Self {
+        TrainConfig {
+            learner: match matches.opt_str("s").unwrap_or("l2svm".to_owned()).as_ref() {
+                "logreg" => Learner::Linear(Solver::LogReg),
+                "l2svm" => Learner::Linear(Solver::L2LossSvm),
+                "l1svm" => Learner::Linear(Solver::L1LossSvm),
+                "mnb" => Learner::Bayes(EventModel::Multinomial),
+                "bnb" => Learner::Bayes(EventModel::Bernoulli),
+                "rocchio" => Learner::Centroid,
+                "knn" => Learner::Knn(neighbors_from_matches(matches)),
+                unk => {
+                    println!("unknown solver: {}", unk);
+                    exit(1)
+                }
+            },
+            alpha: matches.opt_str("a").unwrap_or_default().parse::<f32>().unwrap_or(1.0),
+            cost: matches.opt_str("c").unwrap_or_default().parse::<f32>().unwrap_or(1.0),
+            bias: matches.opt_str("B").unwrap_or_default().parse::<f32>().unwrap_or(1.0),
+            eps: matches.opt_str("e").unwrap_or_default().parse::<f32>().unwrap_or(0.1),
+        }
+    }
+
+    /// trains on either `features` or the terms in `inv_idx` with at least
+    /// `min_freq`, which are the same documents with `labels`.
+    fn train(&self,
+             inv_idx: &InvertedIndex,
+             features: &[SparseVec<f32>],
+             labels: &[usize],
+             min_freq: usize)
+             -> Model {
+        match self.learner {
+            Learner::Linear(solver) => {
+                println!("training {} ...", solver.name());
+                let model = LinearModel::train(solver, features, labels, self.cost, self.bias,
+                                               self.eps);
+                warn_unless_converged(model.converged());
+                Model::Linear(model)
+            }
+            Learner::Bayes(event) => {
+                println!("training {} naive bayes ...", event.name());
+                Model::Bayes(NaiveBayes::train(event, inv_idx, labels, self.alpha, min_freq))
+            }
+            Learner::Centroid => {
+                println!("training nearest centroid ...");
+                Model::Centroid(NearestCentroid::train(features, labels))
+            }
+            Learner::Knn(k) => {
+                println!("training {} nearest neighbours ...", k);
+                Model::Knn(KNearest::train(k, features.to_vec(), labels.to_vec()))
+            }
+        }
+    }
+
+    /// trains one-vs-rest models on `features` with `label_sets` of the
+    /// `class_count` classes; only for the linear solvers.
+    fn train_multi(&self,
+                   features: &[SparseVec<f32>],
+                   label_sets: &[Vec<usize>],
+                   class_count: usize)
+                   -> Model {
+        match self.learner {
+            Learner::Linear(solver) => {
+                println!("training {} one-vs-rest ...", solver.name());
+                let model = OneVsRest::train(solver, features, label_sets, class_count, self.cost,
+                                             self.bias, self.eps);
+                warn_unless_converged(model.converged());
+                Model::OneVsRest(model)
+            }
+            _ => {
+                println!("multi-label training needs a linear solver.");
+                exit(1)
+            }
+        }
+    }
+}
+
+enum Model {
+    Linear(LinearModel),
+    OneVsRest(OneVsRest),
+    Bayes(NaiveBayes),
+    Centroid(NearestCentroid),
+    Knn(KNearest),
+}
+
+impl Model {
+    /// returns the predicted classes of `doc`, exactly one except for
+    /// `OneVsRest`.
+    fn predict(&self, vectorizer: &Vectorizer, doc: &Document) -> Vec<usize> {
+        let features = || vectorizer.transform_terms(doc.terms.iter().cloned(), &doc.tokens);
+        match *self {
+            Model::Linear(ref model) => vec![model.predict(&features())],
+            Model::OneVsRest(ref model) => model.predict(&features()),
+            Model::Bayes(ref model) => vec![model.predict(doc.terms.iter().cloned())],
+            Model::Centroid(ref model) => vec![model.predict(&features())],
+            Model::Knn(ref model) => vec![model.predict(&features())],
+        }
+    }
+
+    fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        match *self {
+            Model::Linear(ref model) => model.save(wtr),
+            Model::OneVsRest(ref model) => model.save(wtr),
+            Model::Bayes(ref model) => model.save(wtr),
+            Model::Centroid(ref model) => model.save(wtr),
+            Model::Knn(ref model) => model.save(wtr),
+        }
+    }
+
+    /// reads any model by its first line, or else a linear one.
+    fn load(path: &str) -> Self {
+        let mut rdr = BufReader::new(open_file(path));
+        let head = rdr.fill_buf().map(|buf| buf.to_vec()).unwrap_or_default();
+        let res_model = if head.starts_with(b"naive_bayes ") {
+            NaiveBayes::load(rdr).map(Model::Bayes)
+        } else if head.starts_with(b"nearest_centroid ") {
+            NearestCentroid::load(rdr).map(Model::Centroid)
+        } else if head.starts_with(b"knn ") {
+            KNearest::load(rdr).map(Model::Knn)
+        } else if head.starts_with(b"one_vs_rest ") {
+            OneVsRest::load(rdr).map(Model::OneVsRest)
+        } else {
+            LinearModel::load(rdr).map(Model::Linear)
+        };
+        match res_model {
+            Ok(model) => model,
+            Err(err) => {
+                println!("cannot load model: {}", err);
+                exit(1)
+            }
+        }
+    }
+}
+
+/// the number of neighbours from `--neighbors`, or `10`.
+fn neighbors_from_matches(matches: &Matches) -> usize {
+    match matches.opt_str("neighbors") {
+        Some(k) => {
+            match k.parse::<usize>() {
+                Ok(k) if 0 < k => k,
+                _ => {
+                    println!("bad number of neighbours: {}", k);
+                    exit(1)
+                }
+            }
+        }
+        None => 10,
+    }
+}
+
+/// warns on stderr when a linear solver stopped at `MAX_ITER`.
+fn warn_unless_converged(converged: bool) {
+    if !converged {
+        eprintln!("warning: reaching the maximum of {} iterations; try a larger `-e`.", MAX_ITER)
+    }
+}
+
+struct CorpusConfig {
+    path_in: String,
+    input: InputFormat,
+    manifest: Option<LabelManifest>,
+    multi_label: bool,
+    stopwords: Stopwords,
+    extractor: NgramExtractor,
+    min_freq: usize,
+    feature: Feature,
+    norm: Norm,
+    selection: Option<(Criterion, usize, bool)>,
+    embeddings: Option<(String, Arc<Embeddings>, Pooling)>,
+    threads: usize,
+}
+
+impl CorpusConfig {
+    fn from_matches(matches: &Matches) -> Self {
+        CorpusConfig {
+            path_in: matches.opt_str("i").unwrap(),
+            input: input_from_matches(matches),
+            manifest: matches.opt_str("L").map(|path| read_manifest(&path)),
+            multi_label: matches.opt_present("L") || matches.opt_present("multi-label"),
+            stopwords: {
+                let (tags, lemmas) = (matches.opt_str("stop-tags"), matches.opt_str("stop-lemmas"));
+                if tags.is_some() || lemmas.is_some() {
+                    match ListStopwordFilter::read(tags.map(|path| BufReader::new(open_file(path))),
+                                                   lemmas.map(|path| BufReader::new(open_file(path)))) {
+                        Ok(filter) => Stopwords::Lists(filter),
+                        Err(err) => {
+                            println!("cannot read stopwords: {}", err);
+                            exit(1)
+                        }
+                    }
+                } else {
+                    let name = matches.opt_str("w").unwrap_or("ptb".to_owned());
+                    match Stopwords::from_name(&name) {
+                        Some(stopwords) => stopwords,
+                        None => {
+                            println!("unknown stopwords: {}", name);
+                            exit(1)
+                        }
+                    }
+                }
+            },
+            extractor: {
+                let name = matches.opt_str("u").unwrap_or("form".to_owned());
+                let unit = match TokenUnit::from_name(&name) {
+                    Some(unit) => unit,
+                    None => {
+                        println!("unknown unit: {}", name);
+                        exit(1)
+                    }
+                };
+                let specs = match parse_specs(&matches.opt_str("n").unwrap_or("1:2^24".to_owned())) {
+                    Ok(specs) => specs,
+                    Err(err) => {
+                        println!("bad ngram spec: {}", err);
+                        exit(1)
+                    }
+                };
+                let name = matches.opt_str("H").unwrap_or("murmur3".to_owned());
+                let algorithm = match HashAlgorithm::from_name(&name) {
+                    Some(algorithm) => algorithm,
+                    None => {
+                        println!("unknown hash: {}", name);
+                        exit(1)
+                    }
+                };
+                let hasher =
+                    FeatureHasher::new(algorithm,
+                                       matches.opt_str("seed").unwrap_or_default().parse::<u32>().unwrap_or(0),
+                                       matches.opt_present("signed"));
+                let needs_clusters = specs.iter().any(|spec| spec.kind == NgramKind::Cluster);
+                let extractor = NgramExtractor::new(unit, specs, hasher);
+                match matches.opt_str("C") {
+                    Some(path) => {
+                        match read_clusters(BufReader::new(open_file(&path))) {
+                            Ok(clusters) => extractor.with_clusters(&path, Arc::new(clusters)),
+                            Err(err) => {
+                                println!("cannot load clusters: {}", err);
+                                exit(1)
                             }
-                        },
-                        _ => None
+                        }
+                    }
+                    None if needs_clusters => {
+                        println!("cluster ngrams need word clusters from `-C`.");
+                        exit(1)
+                    }
+                    None => extractor,
+                }
+            },
+            min_freq: matches.opt_str("t").unwrap_or_default().parse::<usize>().unwrap_or(1),
+            feature: {
+                let name = matches.opt_str("f").unwrap_or("stfidf".to_owned());
+                match Feature::from_name(&name) {
+                    Some(feature) => feature,
+                    None => {
+                        println!("unknown feature: {}", name);
+                        exit(1)
+                    }
+                }
+            },
+            norm: {
+                let name = matches.opt_str("N").unwrap_or("l2".to_owned());
+                match Norm::from_name(&name) {
+                    Some(norm) => norm,
+                    None => {
+                        println!("unknown norm: {}", name);
+                        exit(1)
+                    }
+                }
+            },
+            selection: matches.opt_str("S").map(|name| {
+                let criterion = match Criterion::from_name(&name) {
+                    Some(criterion) => criterion,
+                    None => {
+                        println!("unknown selection criterion: {}", name);
+                        exit(1)
+                    }
+                };
+                (criterion,
+                 matches.opt_str("K").unwrap_or_default().parse::<usize>().unwrap_or(100_000),
+                 matches.opt_present("per-class"))
+            }),
+            embeddings: matches.opt_str("E").map(|path| {
+                let name = matches.opt_str("pooling").unwrap_or("mean".to_owned());
+                let pooling = match Pooling::from_name(&name) {
+                    Some(pooling) => pooling,
+                    None => {
+                        println!("unknown pooling: {}", name);
+                        exit(1)
+                    }
+                };
+                println!("loading embeddings ...");
+                match read_embeddings(&path) {
+                    Ok(embeddings) => (path, Arc::new(embeddings), pooling),
+                    Err(err) => {
+                        println!("cannot load embeddings: {}", err);
+                        exit(1)
                     }
+                }
+            }),
+            threads: threads_from_matches(matches),
+        }
+    }
+
+    fn print_settings(&self) {
+        println!("input: {}", self.input.name());
+        println!("stopwords: {}", self.stopwords.name());
+        println!("unit: {}", self.extractor.unit().name());
+        for spec in self.extractor.specs() {
+            println!("{:>7} dim: {}", spec.name(), spec.dim);
+        }
+    }
+
+    /// reads the corpus, collecting the ngram `names` if given.
+    fn read(&self, names: Option<&mut TermNames>) -> Vec<Document> {
+        self.print_settings();
+        read_corpus(&self.path_in, self.manifest.as_ref(),
+                    &self.reading(self.embeddings.is_some()), names, self.threads)
+    }
+
+    fn reading(&self, keep_tokens: bool) -> ReadConfig<'_> {
+        ReadConfig::new(self.input, &self.stopwords, &self.extractor, keep_tokens)
+    }
+
+    /// returns the labels and the feature matrix for `docs`, together with the
+    /// vectorizer which produces the same features, and the inverted index of
+    /// the selected terms.
+    fn fit<'a, I>(&self, docs: I) -> (Vec<usize>, Vec<SparseVec<f32>>, Vectorizer, InvertedIndex)
+        where I: IntoIterator<Item = &'a Document>
+    {
+        let mut classes = HashMapNumberer::new();
+        let mut labels = Vec::new();
+        let docs = docs.into_iter().collect::<Vec<_>>();
+        for doc in &docs {
+            // the first label is the class.
+            for class in &doc.labels {
+                classes.number(class);
+            }
+            labels.push(classes.number(&doc.class));
+        }
+        // indexes consecutive parts of the documents on their own threads,
+        // and appends the parts in order.
+        let part_len = docs.len().div_ceil(self.threads).max(1);
+        let parts = thread::scope(|scope| {
+            let handles = docs.chunks(part_len)
+                .map(|docs| {
+                    scope.spawn(move || {
+                        let mut inv_idx = InvertedIndex::new();
+                        for doc in docs {
+                            inv_idx.inv_push_signed(doc.terms.iter().cloned());
+                        }
+                        inv_idx
+                    })
                 })
                 .collect::<Vec<_>>();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+        });
+        let mut inv_idx = InvertedIndex::new();
+        for part in parts {
+            inv_idx.append(part);
+        }
+        if let Some((criterion, k, per_class)) = self.selection {
+            let keep = select_terms(&inv_idx, &labels, criterion, k, self.min_freq, per_class);
+            inv_idx.retain_terms(|term| keep.contains(&term));
+            println!("selected by {}: {}", criterion.name(), keep.len());
+        }
+        let mut features = inv_idx.doc_features(&self.feature, &labels, self.norm, self.min_freq);
+        let mut vectorizer = Vectorizer::fit(&inv_idx, &labels, self.stopwords.clone(),
+                                             self.extractor.clone(), self.feature,
+                                             classes.strings().to_vec(), self.min_freq)
+            .with_norm(self.norm);
+        if let Some((ref path, ref embeddings, pooling)) = self.embeddings {
+            let embedder = DocEmbedder::fit(path, embeddings.clone(), pooling,
+                                            docs.iter().map(|doc| &doc.tokens));
+            vectorizer = vectorizer.with_embedder(embedder);
+            features = features.into_iter()
+                .zip(&docs)
+                .map(|(feat_vec, doc)| vectorizer.append_embedding(feat_vec, &doc.tokens))
+                .collect();
+        }
+        (labels, features, vectorizer, inv_idx)
+    }
+}
+
+/// a document with its position in the corpus, its class, read from the
+/// name of its directory, its hashed ngrams with their signs, and its tokens
+/// when needed for embeddings. with a label manifest, the labels are read
+/// from there, and the class is the first one; labels given in the file
+/// itself, as in json lines, take precedence over both.
+struct Document {
+    index: usize,
+    path: PathBuf,
+    class: String,
+    labels: Vec<String>,
+    terms: Vec<(usize, isize)>,
+    tokens: Vec<String>,
+}
 
-            let sentinel = Vec::<usize>::new();
-            let mut terms: Box<Iterator<Item = usize>> =
-                Box::new(sentinel.iter().map(ToOwned::to_owned));
-            if n1 > 0 {
-                terms = Box::new(terms.chain(tokens.iter()
-                                             .map(|x| hash_code(x) % n1)));
+/// how the corpus files are read into documents.
+#[derive(Clone,Copy)]
+struct ReadConfig<'a> {
+    input: InputFormat,
+    filter: &'a Stopwords,
+    extractor: &'a NgramExtractor,
+    keep_tokens: bool,
+}
+
+impl<'a> ReadConfig<'a> {
+    /// exits if the extractor needs tags, lemmas, or heads, which `input` lacks.
+    fn new(input: InputFormat,
+           filter: &'a Stopwords,
+           extractor: &'a NgramExtractor,
+           keep_tokens: bool)
+           -> Self {
+        if !input.is_tagged() {
+            match extractor.unit() {
+                TokenUnit::Lemma | TokenUnit::LemmaPos | TokenUnit::Pos => {
+                    println!("the unit `{}` needs a tagged input format.", extractor.unit().name());
+                    exit(1)
+                }
+                _ => {}
             }
-            if n2 > 0 {
-                terms = Box::new(terms.chain(tokens.iter()
-                                             .zip(tokens.iter().skip(1))
-                                             .map(|x| n1 + (hash_code(x) % n2))));
+            if extractor.specs().iter().any(|spec| spec.kind.is_syntactic()) {
+                println!("dependency ngrams need a tagged input format.");
+                exit(1)
             }
-            if n3 > 0 {
-                terms = Box::new(terms.chain(tokens.iter()
-                                             .zip(tokens.iter().skip(1))
-                                             .zip(tokens.iter().skip(2))
-                                             .map(|x| n1 + n2 + (hash_code(x) % n3))));
+        }
+        ReadConfig {
+            input,
+            filter,
+            extractor,
+            keep_tokens,
+        }
+    }
+
+    /// reads for transforming with `vectorizer`.
+    fn from_vectorizer(input: InputFormat, vectorizer: &'a Vectorizer) -> Self {
+        ReadConfig::new(input, vectorizer.stopwords(), vectorizer.extractor(),
+                        vectorizer.embedder().is_some())
+    }
+}
+
+/// reads the documents under `path_in` on `threads` threads, collecting the
+/// ngram names into `names` if given; see `read_documents`.
+fn read_corpus(path_in: &str,
+               manifest: Option<&LabelManifest>,
+               reading: &ReadConfig,
+               names: Option<&mut TermNames>,
+               threads: usize)
+               -> Vec<Document> {
+    read_documents(&corpus_files(path_in, manifest), 0, reading, names, threads)
+}
+
+/// returns the files under `path_in` with their labels, from `manifest` if
+/// given, or else from the names of their directories; unlabelled files are
+/// skipped.
+fn corpus_files(path_in: &str, manifest: Option<&LabelManifest>) -> Vec<(PathBuf, Vec<String>)> {
+    let mut files = Vec::new();
+    for file_path in iter_file_paths(path_in) {
+        let labels = match manifest {
+            Some(manifest) => {
+                match manifest.labels(&file_path, Path::new(path_in)) {
+                    None => continue,
+                    Some(labels) => labels.to_vec(),
+                }
             }
+            None => {
+                match file_path.parent()
+                    .and_then(Path::file_name)
+                    .and_then(OsStr::to_str) {
+                    None => continue,
+                    Some(class) => vec![class.to_owned()],
+                }
+            }
+        };
+        files.push((file_path, labels));
+    }
+    files
+}
 
-            inv_idx.inv_push(terms);
+/// reads `files` with their labels into the documents numbered from `offset`,
+/// on `threads` threads, each taking a consecutive part of the files, so that
+/// the documents come in the same order as read on one thread. the ngram
+/// names are collected into `names` if given.
+fn read_documents(files: &[(PathBuf, Vec<String>)],
+                  offset: usize,
+                  reading: &ReadConfig,
+                  names: Option<&mut TermNames>,
+                  threads: usize)
+                  -> Vec<Document> {
+    let collect_names = names.is_some();
+    let part_len = files.len().div_ceil(threads).max(1);
+    let parts = thread::scope(|scope| {
+        let handles = files.chunks(part_len)
+            .map(|files| {
+                scope.spawn(move || {
+                    let mut part_names = TermNames::new();
+                    let mut docs = Vec::new();
+                    for (path, labels) in files {
+                        let names = if collect_names { Some(&mut part_names) } else { None };
+                        read_document(path, labels, reading, names, &mut docs)
+                    }
+                    (docs, part_names)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    let mut docs = Vec::with_capacity(files.len());
+    let mut names = names;
+    for (part_docs, part_names) in parts {
+        docs.extend(part_docs);
+        if let Some(ref mut names) = names {
+            for (term, term_names) in part_names {
+                names.entry(term).or_default().extend(term_names)
+            }
         }
+    }
+    for (index, doc) in docs.iter_mut().enumerate() {
+        doc.index = offset + index
+    }
+    docs
+}
 
-        (labels, inv_idx.doc_features(feat_fn, min_freq))
+/// reads the documents in the file at `file_path` into `docs`, with `labels`
+/// unless they come with their own. the documents are numbered later by
+/// `read_documents`.
+fn read_document(file_path: &Path,
+                 labels: &[String],
+                 reading: &ReadConfig,
+                 names: Option<&mut TermNames>,
+                 docs: &mut Vec<Document>) {
+    let source_docs = match reading.input.read(BufReader::new(open_file(file_path))) {
+        Ok(source_docs) => source_docs,
+        Err(err) => {
+            println!("cannot read {}: {}", file_path.display(), err);
+            exit(1)
+        }
     };
-
-    let mut wtr = BufWriter::new(create_file(path_out));
-    for (label, dim2feat) in labels.iter().zip(features.iter()) {
-        write!(wtr, "{}", label).unwrap();
-        for &(dim, feat) in dim2feat {
-            write!(wtr, " {}:{}", dim + 1, feat).unwrap();
+    let mut names = names;
+    for source_doc in source_docs {
+        let labels = if source_doc.labels.is_empty() { labels } else { &source_doc.labels[..] };
+        if labels.is_empty() {
+            continue;
         }
-        writeln!(wtr).unwrap();
+        let (terms, tokens) = document_terms(reading.filter, reading.extractor,
+                                             &source_doc.sentences, reading.input.is_tagged(),
+                                             names.as_deref_mut());
+        let path = match source_doc.id {
+            Some(id) => PathBuf::from(format!("{}:{}", file_path.display(), id)),
+            None => file_path.to_owned(),
+        };
+        docs.push(Document {
+            index: 0,
+            path,
+            class: labels[0].clone(),
+            labels: labels.to_vec(),
+            terms,
+            tokens: if reading.keep_tokens { tokens } else { Vec::new() },
+        })
     }
 }
 
-fn hash_code<T>(x: T) -> usize
-    where T: Hash
-{
-    let mut hasher = DefaultHasher::new();
-    x.hash(&mut hasher);
-    hasher.finish() as usize
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// reads and fits the corpus in `dir` with `args` on `threads` threads,
+    /// returning the documents, the ngram names, the features, and the
+    /// saved vectorizer.
+    fn read_and_fit(dir: &Path, args: &[&str], threads: usize)
+                    -> (Vec<Document>, TermNames, Vec<SparseVec<f32>>, Vec<u8>) {
+        let mut args = args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+        args.extend(vec!["-i".to_owned(), dir.display().to_string(),
+                         "-j".to_owned(), threads.to_string()]);
+        let corpus = CorpusConfig::from_matches(&parse_args(corpus_options(&mut Options::new()),
+                                                            &args));
+        let mut names = TermNames::new();
+        let docs = corpus.read(Some(&mut names));
+        let (_, features, vectorizer, _) = corpus.fit(&docs);
+        let mut saved = Vec::new();
+        vectorizer.save(&mut saved).unwrap();
+        (docs, names, features, saved)
+    }
+
+    #[test]
+    fn threads_give_the_same_output() {
+        let dir = env::temp_dir().join(format!("doc-class-test-threads-{}", std::process::id()));
+        let words = ["the", "cat", "sat", "on", "a", "mat", "dog", "ran", "far", "away"];
+        for doc in 0..11 {
+            let class_dir = dir.join(if doc % 3 == 0 { "pets" } else { "places" });
+            fs::create_dir_all(&class_dir).unwrap();
+            let text = (0..3)
+                .map(|sent| {
+                    (0..4 + sent).map(|i| words[(doc * 7 + sent * 3 + i * i) % words.len()])
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            fs::write(class_dir.join(format!("{}.txt", doc)), text).unwrap();
+        }
+        let args = ["-I", "text", "-n", "1:2^10,2:2^10,c2-3:2^10", "--signed", "-f", "deltatfidf"];
+        let (docs, names, features, saved) = read_and_fit(&dir, &args, 1);
+        assert_eq!(docs.len(), 11);
+        assert!(!names.is_empty());
+        for threads in 2..5 {
+            let (other_docs, other_names, other_features, other_saved) =
+                read_and_fit(&dir, &args, threads);
+            assert_eq!(docs.len(), other_docs.len());
+            for (doc, other) in docs.iter().zip(&other_docs) {
+                assert_eq!(doc.index, other.index);
+                assert_eq!(doc.path, other.path);
+                assert_eq!(doc.labels, other.labels);
+                assert_eq!(doc.terms, other.terms);
+            }
+            assert_eq!(names, other_names);
+            assert_eq!(features, other_features);
+            assert_eq!(saved, other_saved);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,474 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::SparseVec;
+use inverted_index::{TermStats, mean, class_sizes};
+use weighting::{Weighting, TermScope, Norm};
+use protocoll::MapMut;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// the estimated memory for a key of a block, besides its entries.
+const KEY_BYTES: usize = 64;
+
+/// numbers the run files of this process.
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// values kept in the runs, written in little-endian.
+trait Value: Copy {
+    fn write_to<W: Write>(self, wtr: &mut W) -> io::Result<()>;
+    fn read_from<R: Read>(rdr: &mut R) -> io::Result<Self>;
+}
+
+impl Value for isize {
+    fn write_to<W: Write>(self, wtr: &mut W) -> io::Result<()> {
+        wtr.write_all(&(self as i64).to_le_bytes())
+    }
+
+    fn read_from<R: Read>(rdr: &mut R) -> io::Result<Self> {
+        let mut buf = [0; 8];
+        rdr.read_exact(&mut buf)?;
+        Ok(i64::from_le_bytes(buf) as isize)
+    }
+}
+
+impl Value for f32 {
+    fn write_to<W: Write>(self, wtr: &mut W) -> io::Result<()> {
+        wtr.write_all(&self.to_bits().to_le_bytes())
+    }
+
+    fn read_from<R: Read>(rdr: &mut R) -> io::Result<Self> {
+        let mut buf = [0; 4];
+        rdr.read_exact(&mut buf)?;
+        Ok(f32::from_bits(u32::from_le_bytes(buf)))
+    }
+}
+
+fn write_usize<W: Write>(wtr: &mut W, n: usize) -> io::Result<()> {
+    wtr.write_all(&(n as u64).to_le_bytes())
+}
+
+fn read_usize<R: Read>(rdr: &mut R) -> io::Result<usize> {
+    let mut buf = [0; 8];
+    rdr.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf) as usize)
+}
+
+/// lists of `(index, value)` pairs by key, collected in a block in memory,
+/// and written to a new run file sorted by key whenever the block exceeds
+/// the budget. the pairs of each key must come in ascending order of index
+/// over all runs, so that merging the runs only needs to concatenate them.
+/// the run files are removed when dropped.
+struct Runs<T> {
+    dir: PathBuf,
+    budget: usize,
+    block: HashMap<usize, Vec<(usize, T)>>,
+    block_size: usize,
+    paths: Vec<PathBuf>,
+}
+
+impl<T> Runs<T>
+    where T: Value
+{
+    fn new(dir: &Path, budget: usize) -> Self {
+        Runs {
+            dir: dir.to_owned(),
+            budget,
+            block: HashMap::new(),
+            block_size: 0,
+            paths: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, key: usize, index: usize, value: T) {
+        if !self.block.contains_key(&key) {
+            self.block_size += KEY_BYTES
+        }
+        self.block.entry(key).or_default().push((index, value));
+        self.block_size += size_of::<(usize, T)>()
+    }
+
+    fn spill_if_full(&mut self) -> io::Result<()> {
+        if self.budget <= self.block_size {
+            self.spill()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// writes the block as a run: the number of keys, then for each key in
+    /// ascending order, the key, the number of its pairs, and the pairs.
+    fn spill(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let path = self.dir.join(format!("doc-class-{}-{}.run",
+                                         process::id(),
+                                         RUN_COUNT.fetch_add(1, Ordering::SeqCst)));
+        let mut wtr = BufWriter::new(File::create(&path)?);
+        self.paths.push(path);
+        let mut block = self.block.drain().collect::<Vec<_>>();
+        self.block_size = 0;
+        block.sort_by_key(|&(key, _)| key);
+        write_usize(&mut wtr, block.len())?;
+        for (key, pairs) in block {
+            write_usize(&mut wtr, key)?;
+            write_usize(&mut wtr, pairs.len())?;
+            for (index, value) in pairs {
+                write_usize(&mut wtr, index)?;
+                value.write_to(&mut wtr)?;
+            }
+        }
+        wtr.flush()
+    }
+
+    /// merges the runs written so far, without the block.
+    fn merge(&self) -> io::Result<Merge<T>> {
+        let mut merge = Merge {
+            rdrs: Vec::with_capacity(self.paths.len()),
+            heads: Vec::with_capacity(self.paths.len()),
+            heap: BinaryHeap::new(),
+        };
+        for (run, path) in self.paths.iter().enumerate() {
+            let mut rdr = BufReader::new(File::open(path)?);
+            let key_count = read_usize(&mut rdr)?;
+            merge.rdrs.push((rdr, key_count));
+            merge.heads.push(Vec::new());
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+}
+
+impl<T> Drop for Runs<T> {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// the k-way merge of runs, returning each key in ascending order with its
+/// pairs from all runs, in the order of the runs.
+struct Merge<T> {
+    /// the reader of each run, with the number of keys left.
+    rdrs: Vec<(BufReader<File>, usize)>,
+    /// the pairs of the current key of each run.
+    heads: Vec<Vec<(usize, T)>>,
+    /// the current key of each run, by which the runs are ordered.
+    heap: BinaryHeap<Reverse<(usize, usize)>>,
+}
+
+impl<T> Merge<T>
+    where T: Value
+{
+    /// reads the next key of `run` into its head.
+    fn advance(&mut self, run: usize) -> io::Result<()> {
+        let (ref mut rdr, ref mut key_count) = self.rdrs[run];
+        if *key_count == 0 {
+            return Ok(());
+        }
+        *key_count -= 1;
+        let key = read_usize(rdr)?;
+        let len = read_usize(rdr)?;
+        let mut pairs = Vec::with_capacity(len);
+        for _ in 0..len {
+            let index = read_usize(rdr)?;
+            pairs.push((index, T::read_from(rdr)?));
+        }
+        self.heads[run] = pairs;
+        self.heap.push(Reverse((key, run)));
+        Ok(())
+    }
+}
+
+impl<T> Iterator for Merge<T>
+    where T: Value
+{
+    type Item = io::Result<(usize, Vec<(usize, T)>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((key, run)) = self.heap.pop()?;
+        let mut pairs = Vec::new();
+        let mut run = run;
+        loop {
+            pairs.append(&mut self.heads[run]);
+            if let Err(err) = self.advance(run) {
+                return Some(Err(err));
+            }
+            match self.heap.peek() {
+                Some(&Reverse((next_key, next_run))) if next_key == key => run = next_run,
+                _ => break,
+            }
+            self.heap.pop();
+        }
+        Some(Ok((key, pairs)))
+    }
+}
+
+/// builds an inverted index which does not fit into memory, in the manner of
+/// single-pass in-memory indexing (SPIMI): the postings are collected by term
+/// in memory until the budget is reached, then written to disk as a run
+/// sorted by term. the runs are merged when reading the index.
+pub struct BlockedIndexBuilder {
+    runs: Runs<isize>,
+    doc_count: usize,
+}
+
+impl BlockedIndexBuilder {
+    /// writes the runs into `dir`, each holding roughly `budget` bytes of
+    /// postings in memory.
+    pub fn new<P>(dir: P, budget: usize) -> Self
+        where P: AsRef<Path>
+    {
+        BlockedIndexBuilder {
+            runs: Runs::new(dir.as_ref(), budget),
+            doc_count: 0,
+        }
+    }
+
+    /// inserts a new document with `(term, sign)` pairs, as
+    /// `InvertedIndex::inv_push_signed`.
+    pub fn push_signed<I>(&mut self, terms: I) -> io::Result<()>
+        where I: Iterator<Item = (usize, isize)>
+    {
+        let doc = self.doc_count;
+        self.doc_count += 1;
+        let mut term2tf = SparseVec::new();
+        for (term, sign) in terms {
+            term2tf.update_mut(term, 0, |tf| *tf += sign)
+        }
+        for (term, tf) in term2tf {
+            if tf != 0 {
+                self.runs.push(term, doc, tf)
+            }
+        }
+        // a document never spans two runs.
+        self.runs.spill_if_full()
+    }
+
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    /// writes the last run.
+    pub fn finish(mut self) -> io::Result<BlockedIndex> {
+        self.runs.spill()?;
+        Ok(BlockedIndex {
+            runs: self.runs,
+            doc_count: self.doc_count,
+        })
+    }
+}
+
+/// an inverted index in sorted runs on disk, from `BlockedIndexBuilder`,
+/// which are removed when dropped. it gives the same statistics and
+/// features as an `InvertedIndex` with the same documents.
+pub struct BlockedIndex {
+    runs: Runs<isize>,
+    doc_count: usize,
+}
+
+impl BlockedIndex {
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    /// the number of runs on disk.
+    pub fn run_count(&self) -> usize {
+        self.runs.paths.len()
+    }
+
+    /// as `InvertedIndex::term_stats`, merging the runs once.
+    pub fn term_stats(&self,
+                      labels: &[usize],
+                      class_count: usize,
+                      min_freq: usize,
+                      with_class_dfs: bool)
+                      -> io::Result<TermStats> {
+        let mut stats = TermStats {
+            doc_count: self.doc_count,
+            min_freq,
+            doc_lens: vec![0; self.doc_count],
+            ..TermStats::default()
+        };
+        for res in self.runs.merge()? {
+            let (term, doc2tf) = res?;
+            if doc2tf.len() < min_freq {
+                continue;
+            }
+            stats.terms.push(term);
+            stats.doc_freqs.push(doc2tf.len());
+            if with_class_dfs {
+                let mut class_dfs = vec![0; class_count];
+                for &(doc, _) in &doc2tf {
+                    class_dfs[labels[doc]] += 1
+                }
+                stats.class_dfs.push(class_dfs);
+            }
+            for (doc, tf) in doc2tf {
+                stats.doc_lens[doc] += tf.unsigned_abs()
+            }
+        }
+        Ok(stats)
+    }
+
+    /// as `InvertedIndex::doc_features`, with the terms and document lengths
+    /// from `stats`, returning the feature vectors one document at a time.
+    /// the features are sorted by document through another set of runs with
+    /// the same budget.
+    pub fn doc_features<W>(&self,
+                           stats: &TermStats,
+                           weighting: &W,
+                           labels: &[usize],
+                           norm: Norm)
+                           -> io::Result<FeatureRows>
+        where W: Weighting + ?Sized
+    {
+        let avg_doc_len = mean(&stats.doc_lens);
+        let class_sizes = class_sizes(labels);
+        let mut rows = Runs::new(&self.runs.dir, self.runs.budget);
+        let mut dim = 0;
+        for res in self.runs.merge()? {
+            let (term, doc2tf) = res?;
+            if dim == stats.terms.len() || stats.terms[dim] != term {
+                continue;
+            }
+            let class_dfs = if weighting.needs_classes() {
+                let mut class_dfs = vec![0; class_sizes.len()];
+                for &(doc, _) in &doc2tf {
+                    class_dfs[labels[doc]] += 1
+                }
+                class_dfs
+            } else {
+                Vec::new()
+            };
+            let scope = TermScope {
+                avg_doc_len,
+                df: doc2tf.len(),
+                doc_count: self.doc_count,
+                class_dfs: &class_dfs,
+                class_sizes: &class_sizes,
+            };
+            for &(doc, tf) in &doc2tf {
+                rows.push(doc, dim, scope.signed_weight(weighting, tf, stats.doc_lens[doc]));
+            }
+            rows.spill_if_full()?;
+            dim += 1;
+        }
+        rows.spill()?;
+        Ok(FeatureRows {
+            merge: rows.merge()?,
+            _runs: rows,
+            norm,
+            doc: 0,
+            doc_count: self.doc_count,
+            next_row: None,
+        })
+    }
+}
+
+/// the scaled feature vectors of the documents of a `BlockedIndex` in order,
+/// merged from runs on disk, which are removed when dropped.
+pub struct FeatureRows {
+    merge: Merge<f32>,
+    /// kept for removing the run files when dropped.
+    _runs: Runs<f32>,
+    norm: Norm,
+    doc: usize,
+    doc_count: usize,
+    /// the next document with features, and its features.
+    next_row: Option<(usize, Vec<(usize, f32)>)>,
+}
+
+impl Iterator for FeatureRows {
+    type Item = io::Result<SparseVec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.doc == self.doc_count {
+            return None;
+        }
+        if self.next_row.is_none() {
+            match self.merge.next() {
+                Some(Ok(row)) => self.next_row = Some(row),
+                Some(Err(err)) => return Some(Err(err)),
+                None => {}
+            }
+        }
+        let mut feat_vec = match self.next_row {
+            Some((doc, _)) if doc == self.doc => {
+                self.next_row.take().map_or_else(SparseVec::new, |(_, row)| row.into_iter().collect())
+            }
+            _ => SparseVec::new(),
+        };
+        self.norm.apply(&mut feat_vec);
+        self.doc += 1;
+        Some(Ok(feat_vec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inverted_index::InvertedIndex;
+    use weighting::Feature;
+
+    /// documents of signed terms from a small linear congruential generator,
+    /// with two classes.
+    fn docs() -> (Vec<Vec<(usize, isize)>>, Vec<usize>) {
+        let mut seed = 7u64;
+        let mut next = move |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as usize
+        };
+        let mut docs = Vec::new();
+        let mut labels = Vec::new();
+        for doc in 0..24 {
+            let len = 1 + next(12);
+            let terms = (0..len)
+                .map(|_| (next(30), if next(4) == 0 { -1 } else { 1 }))
+                .collect();
+            docs.push(terms);
+            labels.push(doc % 2);
+        }
+        (docs, labels)
+    }
+
+    #[test]
+    fn blocked_index_matches_inverted_index() {
+        let (docs, labels) = docs();
+        let dir = std::env::temp_dir().join(format!("doc-class-test-blocked-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut inv_idx = InvertedIndex::new();
+        let mut builder = BlockedIndexBuilder::new(&dir, 64);
+        for terms in &docs {
+            inv_idx.inv_push_signed(terms.iter().cloned());
+            builder.push_signed(terms.iter().cloned()).unwrap();
+        }
+        let blk_idx = builder.finish().unwrap();
+        assert_eq!(blk_idx.doc_count(), docs.len());
+        assert!(blk_idx.run_count() > 1);
+        let norm = Norm::from_name("l2").unwrap();
+        for min_freq in 1..3 {
+            let stats = blk_idx.term_stats(&labels, 2, min_freq, true).unwrap();
+            assert_eq!(stats, inv_idx.term_stats(&labels, 2, min_freq, true));
+            for name in &["binary", "tfidf", "stfidf", "bm25", "pivoted", "probidf", "deltatfidf"] {
+                let feature = Feature::from_name(name).unwrap();
+                let rows = blk_idx.doc_features(&stats, &feature, &labels, norm)
+                    .unwrap()
+                    .collect::<io::Result<Vec<_>>>()
+                    .unwrap();
+                assert_eq!(rows, inv_idx.doc_features(&feature, &labels, norm, min_freq));
+            }
+        }
+        drop(blk_idx);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
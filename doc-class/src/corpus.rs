@@ -0,0 +1,544 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use extraction::Token;
+use error::{LoadError, FormatError};
+use conllx;
+use std::io::BufRead;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// a document as read from a file, before filtering and extraction.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+pub struct SourceDocument {
+    /// tells the document apart from the others in the same file, for
+    /// formats with several documents per file.
+    pub id: Option<String>,
+    /// the labels given in the file, if the format has them.
+    pub labels: Vec<String>,
+    pub sentences: Vec<Vec<Token>>,
+}
+
+/// A `CorpusReader` reads the documents in a file.
+pub trait CorpusReader {
+    fn read<R>(&self, rdr: R) -> Result<Vec<SourceDocument>, LoadError> where R: BufRead;
+}
+
+/// one document per file in conll-x, with the tags of the `POS` column.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct ConllxReader;
+
+impl CorpusReader for ConllxReader {
+    /// sentences which do not parse are read as empty.
+    fn read<R>(&self, rdr: R) -> Result<Vec<SourceDocument>, LoadError>
+        where R: BufRead
+    {
+        let mut sentences = Vec::new();
+        for sentence in conllx::Reader::new(rdr).sentences() {
+            let sentence = sentence.unwrap_or(conllx::Sentence::new(Vec::new()));
+            sentences.push(sentence.iter()
+                .map(|tok| {
+                    Token {
+                        form: tok.form().map(str::to_owned),
+                        lemma: tok.lemma().map(str::to_owned),
+                        pos: tok.pos().map(str::to_owned),
+                        // conll-x numbers the tokens from `1`, with `0` for the root.
+                        head: tok.head().and_then(|h| h.checked_sub(1)),
+                        rel: tok.head_rel().map(str::to_owned),
+                    }
+                })
+                .collect());
+        }
+        Ok(vec![SourceDocument { sentences, ..SourceDocument::default() }])
+    }
+}
+
+/// one document per file in conll-u, with the universal tags of the `UPOS`
+/// column, or the `XPOS` column if asked for. multiword tokens like `1-2` are
+/// read as the syntactic words which follow them, and empty nodes like `1.1`
+/// are skipped, as the heads refer to neither.
+#[derive(Debug,Default,Clone,Copy,PartialEq,Eq)]
+pub struct ConlluReader {
+    pub xpos: bool,
+}
+
+impl CorpusReader for ConlluReader {
+    fn read<R>(&self, rdr: R) -> Result<Vec<SourceDocument>, LoadError>
+        where R: BufRead
+    {
+        let mut sentences = Vec::new();
+        let mut sentence = Vec::new();
+        for res_line in rdr.lines() {
+            let line = res_line?;
+            if line.trim().is_empty() {
+                if !sentence.is_empty() {
+                    sentences.push(sentence);
+                    sentence = Vec::new();
+                }
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() != 10 {
+                return Err(FormatError::new(&line).into());
+            }
+            if fields[0].contains('-') || fields[0].contains('.') {
+                continue;
+            }
+            let column = |k: usize| if fields[k] == "_" { None } else { Some(fields[k].to_owned()) };
+            let head = match fields[6] {
+                "_" => None,
+                head => head.parse::<usize>()?.checked_sub(1),
+            };
+            sentence.push(Token {
+                form: Some(fields[1].to_owned()),
+                lemma: column(2),
+                pos: column(if self.xpos { 4 } else { 3 }),
+                head,
+                rel: column(7),
+            });
+        }
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+        Ok(vec![SourceDocument { sentences, ..SourceDocument::default() }])
+    }
+}
+
+/// splits text into untagged tokens: runs of letters and digits, which may
+/// be joined by apostrophes and hyphens, and by periods and commas between
+/// digits; han and kana characters, which are written without spaces, one
+/// by one; and runs of the same other symbol, like `...`.
+///
+/// # example
+/// ```
+/// use doc_class::corpus::Tokenizer;
+/// let tokenizer = Tokenizer::new(true);
+/// assert_eq!(tokenizer.tokenize("Don't pay $3.50 in Zürich-Nord..."),
+///            ["don't", "pay", "$", "3.50", "in", "zürich-nord", "..."]);
+/// assert_eq!(tokenizer.tokenize("東京へ"), ["東", "京", "へ"]);
+/// ```
+#[derive(Debug,Default,Clone,Copy,PartialEq,Eq)]
+pub struct Tokenizer {
+    lowercase: bool,
+}
+
+impl Tokenizer {
+    pub fn new(lowercase: bool) -> Self {
+        Tokenizer { lowercase }
+    }
+
+    pub fn lowercase(&self) -> bool {
+        self.lowercase
+    }
+
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            let mut token = c.to_string();
+            if is_ideographic(c) {
+                // one character each.
+            } else if c.is_alphanumeric() {
+                let mut last = c;
+                loop {
+                    match chars.peek().cloned() {
+                        Some(next) if next.is_alphanumeric() && !is_ideographic(next) => {}
+                        Some(next) if joins(last, next, &chars) => {}
+                        _ => break,
+                    }
+                    last = chars.next().unwrap();
+                    token.push(last);
+                }
+            } else {
+                while chars.peek() == Some(&c) {
+                    token.push(chars.next().unwrap());
+                }
+            }
+            tokens.push(if self.lowercase { token.to_lowercase() } else { token });
+        }
+        tokens
+    }
+
+    /// returns a sentence of untagged tokens for each line of `text` with any.
+    pub fn sentences(&self, text: &str) -> Vec<Vec<Token>> {
+        text.lines()
+            .map(|line| self.tokenize(line).iter().map(|form| Token::untagged(form)).collect::<Vec<_>>())
+            .filter(|sentence| !sentence.is_empty())
+            .collect()
+    }
+}
+
+/// whether `c` is a han or kana character.
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32,
+             0x3040..=0x30ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff |
+             0x20000..=0x2fa1f)
+}
+
+/// whether `mark` after `last` joins the word with the character following
+/// `mark`, which `chars` peeks at.
+fn joins(last: char, mark: char, chars: &Peekable<Chars>) -> bool {
+    let mut ahead = chars.clone();
+    ahead.next();
+    let after = match ahead.next() {
+        Some(after) if after.is_alphanumeric() && !is_ideographic(after) => after,
+        _ => return false,
+    };
+    match mark {
+        '\'' | '’' | '-' => true,
+        '.' | ',' => last.is_numeric() && after.is_numeric(),
+        _ => false,
+    }
+}
+
+/// one document per file of utf-8 text, split into tokens by a `Tokenizer`,
+/// with one sentence per line.
+#[derive(Debug,Default,Clone,Copy,PartialEq,Eq)]
+pub struct TextReader {
+    pub tokenizer: Tokenizer,
+}
+
+impl CorpusReader for TextReader {
+    fn read<R>(&self, mut rdr: R) -> Result<Vec<SourceDocument>, LoadError>
+        where R: BufRead
+    {
+        let mut text = String::new();
+        rdr.read_to_string(&mut text)?;
+        Ok(vec![SourceDocument { sentences: self.tokenizer.sentences(&text), ..SourceDocument::default() }])
+    }
+}
+
+/// one document per line of json lines, from objects with the field `text`,
+/// split into tokens by a `Tokenizer`, and with the field `label` for a
+/// label, or a list of labels, if given. the field `id` is kept, or else the
+/// line number from `1`. empty lines are skipped.
+#[derive(Debug,Default,Clone,Copy,PartialEq,Eq)]
+pub struct JsonlReader {
+    pub tokenizer: Tokenizer,
+}
+
+impl CorpusReader for JsonlReader {
+    fn read<R>(&self, rdr: R) -> Result<Vec<SourceDocument>, LoadError>
+        where R: BufRead
+    {
+        let mut docs = Vec::new();
+        for (n, res_line) in rdr.lines().enumerate() {
+            let line = res_line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = match parse_json(&line) {
+                Some(Json::Object(fields)) => fields,
+                _ => return Err(FormatError::new(&format!("no json object in line {}", n + 1)).into()),
+            };
+            let mut doc = SourceDocument::default();
+            let mut text = None;
+            for (key, value) in fields {
+                match (key.as_ref(), value) {
+                    ("text", Json::String(s)) => text = Some(s),
+                    ("label", Json::String(label)) => doc.labels = vec![label],
+                    ("label", Json::Array(labels)) => {
+                        for label in labels {
+                            match label {
+                                Json::String(label) => doc.labels.push(label),
+                                _ => return Err(FormatError::new(&format!("bad label in line {}", n + 1)).into()),
+                            }
+                        }
+                    }
+                    ("label", Json::Null) => {}
+                    ("id", Json::String(id)) => doc.id = Some(id),
+                    ("id", Json::Number(id)) => doc.id = Some(id),
+                    ("text", _) | ("label", _) | ("id", _) => {
+                        return Err(FormatError::new(&format!("bad {} in line {}", key, n + 1)).into())
+                    }
+                    _ => {}
+                }
+            }
+            let text = text.ok_or_else(|| FormatError::new(&format!("no text in line {}", n + 1)))?;
+            doc.sentences = self.tokenizer.sentences(&text);
+            doc.id = doc.id.or_else(|| Some((n + 1).to_string()));
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+}
+
+/// One of the readers above, chosen at run time.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum InputFormat {
+    Conllx(ConllxReader),
+    Conllu(ConlluReader),
+    Text(TextReader),
+    Jsonl(JsonlReader),
+}
+
+impl InputFormat {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            InputFormat::Conllx(_) => "conllx",
+            InputFormat::Conllu(ConlluReader { xpos: false }) => "conllu",
+            InputFormat::Conllu(ConlluReader { xpos: true }) => "conllu-xpos",
+            InputFormat::Text(_) => "text",
+            InputFormat::Jsonl(_) => "jsonl",
+        }
+    }
+
+    /// returns the readers by their names, with the raw text ones not
+    /// lowercasing; see `with_lowercase`.
+    pub fn from_name(name: &str) -> Option<InputFormat> {
+        match name {
+            "conllx" => Some(InputFormat::Conllx(ConllxReader)),
+            "conllu" => Some(InputFormat::Conllu(ConlluReader { xpos: false })),
+            "conllu-xpos" => Some(InputFormat::Conllu(ConlluReader { xpos: true })),
+            "text" => Some(InputFormat::Text(TextReader::default())),
+            "jsonl" => Some(InputFormat::Jsonl(JsonlReader::default())),
+            _ => None,
+        }
+    }
+
+    /// sets whether the tokenizer of the raw text readers lowercases.
+    pub fn with_lowercase(self, lowercase: bool) -> Self {
+        let tokenizer = Tokenizer::new(lowercase);
+        match self {
+            InputFormat::Text(_) => InputFormat::Text(TextReader { tokenizer }),
+            InputFormat::Jsonl(_) => InputFormat::Jsonl(JsonlReader { tokenizer }),
+            format => format,
+        }
+    }
+
+    /// whether the tokens come with tags, lemmas, and heads.
+    pub fn is_tagged(&self) -> bool {
+        matches!(*self, InputFormat::Conllx(_) | InputFormat::Conllu(_))
+    }
+}
+
+impl CorpusReader for InputFormat {
+    fn read<R>(&self, rdr: R) -> Result<Vec<SourceDocument>, LoadError>
+        where R: BufRead
+    {
+        match *self {
+            InputFormat::Conllx(ref reader) => reader.read(rdr),
+            InputFormat::Conllu(ref reader) => reader.read(rdr),
+            InputFormat::Text(ref reader) => reader.read(rdr),
+            InputFormat::Jsonl(ref reader) => reader.read(rdr),
+        }
+    }
+}
+
+/// a json value, with numbers kept as written.
+#[derive(Debug,Clone,PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// parses `s` as a single json value, or returns `None` if it is none.
+fn parse_json(s: &str) -> Option<Json> {
+    let mut chars = s.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_space(&mut chars);
+    if chars.next().is_none() { Some(value) } else { None }
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_space(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_space(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Some(Json::Object(fields));
+            }
+            loop {
+                skip_space(chars);
+                if chars.next()? != '"' {
+                    return None;
+                }
+                let key = parse_string(chars)?;
+                skip_space(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                fields.push((key, parse_value(chars)?));
+                skip_space(chars);
+                match chars.next()? {
+                    ',' => {}
+                    '}' => return Some(Json::Object(fields)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_space(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+                skip_space(chars);
+                match chars.next()? {
+                    ',' => {}
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        _ => {
+            let mut word = String::new();
+            while chars.peek().is_some_and(|&c| c.is_alphanumeric() || "+-.".contains(c)) {
+                word.push(chars.next().unwrap());
+            }
+            match word.as_ref() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                _ if word.parse::<f64>().is_ok() => Some(Json::Number(word)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// parses the rest of a string after its opening quote.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => {
+                match chars.next()? {
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let mut code = parse_hex4(chars)?;
+                        // a surrogate pair for a character beyond the basic plane.
+                        if (0xd800..0xdc00).contains(&code) {
+                            if chars.next()? != '\\' || chars.next()? != 'u' {
+                                return None;
+                            }
+                            let low = parse_hex4(chars)?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return None;
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        s.push(::std::char::from_u32(code)?)
+                    }
+                    c => s.push(c),
+                }
+            }
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forms(sentence: &[Token]) -> Vec<&str> {
+        sentence.iter().map(|tok| tok.form.as_ref().unwrap().as_str()).collect()
+    }
+
+    #[test]
+    fn conllu_reads_syntactic_words() {
+        let conllu = "# text = Je parle du chat.\n\
+                      1\tJe\til\tPRON\tPRP\t_\t2\tnsubj\t_\t_\n\
+                      2\tparle\tparler\tVERB\tVBZ\t_\t0\troot\t_\t_\n\
+                      3-4\tdu\t_\t_\t_\t_\t_\t_\t_\t_\n\
+                      3\tde\tde\tADP\tIN\t_\t5\tcase\t_\t_\n\
+                      4\tle\tle\tDET\tDT\t_\t5\tdet\t_\t_\n\
+                      4.1\tvu\tvoir\tVERB\tVBN\t_\t_\t_\t2:conj\t_\n\
+                      5\tchat\tchat\tNOUN\tNN\t_\t2\tobl\t_\t_\n\
+                      \n\
+                      1\tOui\toui\tINTJ\tUH\t_\t0\troot\t_\t_\n";
+        let docs = ConlluReader { xpos: false }.read(conllu.as_bytes()).unwrap();
+        assert_eq!(docs.len(), 1);
+        let sentences = &docs[0].sentences;
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(forms(&sentences[0]), ["Je", "parle", "de", "le", "chat"]);
+        assert_eq!(sentences[0].iter().map(|tok| tok.head).collect::<Vec<_>>(),
+                   [Some(1), None, Some(4), Some(4), Some(1)]);
+        assert_eq!(sentences[0][2].lemma.as_ref().unwrap(), "de");
+        assert_eq!(sentences[0][2].pos.as_ref().unwrap(), "ADP");
+        assert_eq!(sentences[0][4].rel.as_ref().unwrap(), "obl");
+        let docs = ConlluReader { xpos: true }.read(conllu.as_bytes()).unwrap();
+        assert_eq!(docs[0].sentences[0][2].pos.as_ref().unwrap(), "IN");
+        assert!(ConlluReader::default().read("1\tJe\til\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn jsonl_reads_escapes_labels_and_ids() {
+        let jsonl = "{\"id\": \"a\", \"text\": \"caf\\u00e9 \\\"ok\\\"\\nsmile \\ud83d\\ude00\", \
+                     \"label\": \"pos\"}\n\
+                     \n\
+                     { \"label\" : [\"x\", \"y\"], \"text\" : \"two\\tlabels\", \"id\": 7, \
+                     \"extra\": {\"nested\": [1, true, null]} }\n\
+                     {\"text\": \"\", \"label\": null}\n";
+        let docs = JsonlReader::default().read(jsonl.as_bytes()).unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[0].id.as_ref().unwrap(), "a");
+        assert_eq!(docs[0].labels, ["pos"]);
+        assert_eq!(docs[0].sentences.len(), 2);
+        assert_eq!(forms(&docs[0].sentences[0]), ["café", "\"", "ok", "\""]);
+        assert_eq!(forms(&docs[0].sentences[1]), ["smile", "\u{1f600}"]);
+        assert_eq!(docs[1].id.as_ref().unwrap(), "7");
+        assert_eq!(docs[1].labels, ["x", "y"]);
+        assert_eq!(forms(&docs[1].sentences[0]), ["two", "labels"]);
+        // the line number, counting the empty line.
+        assert_eq!(docs[2].id.as_ref().unwrap(), "4");
+        assert!(docs[2].labels.is_empty());
+        assert!(docs[2].sentences.is_empty());
+    }
+
+    #[test]
+    fn jsonl_rejects_bad_lines() {
+        let reader = JsonlReader::default();
+        for line in &["{\"label\": \"pos\"}",
+                      "{\"text\": 1}",
+                      "{\"text\": \"a\", \"label\": [1]}",
+                      "{\"text\": \"a\"",
+                      "{\"text\": \"a\"} x",
+                      "[\"text\"]",
+                      "{\"text\": \"\\ud83d\"}",
+                      "{\"text\": \"\\u00zz\"}"] {
+            assert!(reader.read(line.as_bytes()).is_err(), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::SparseVec;
+use formats::{to_csr, from_csr};
+use error::FormatError;
+use std::ops::Range;
+
+/// a sparse matrix in compressed sparse rows, as scipy keeps it: the values
+/// of row `i` are `data[indptr[i]..indptr[i + 1]]`, in the columns at the
+/// same positions of `indices`, which are ascending within each row.
+///
+/// # example
+/// ```
+/// use doc_class::csr_matrix::CsrMatrix;
+/// let rows = vec![vec![(0, 1.0), (2, 2.0)].into_iter().collect(),
+///                 vec![(1, 3.0)].into_iter().collect()];
+/// let m = CsrMatrix::from_rows(&rows, 3);
+/// assert_eq!(m.mul_vec(&[1.0, 1.0, 1.0]), [3.0, 3.0]);
+/// assert_eq!(m.transpose().row(2), vec![(0, 2.0)].into_iter().collect());
+/// assert_eq!(m.slice_cols(1..3).to_rows(), [vec![(1, 2.0)].into_iter().collect(),
+///                                           vec![(0, 3.0)].into_iter().collect()]);
+/// ```
+#[derive(Debug,Clone,PartialEq)]
+pub struct CsrMatrix {
+    cols: usize,
+    data: Vec<f32>,
+    indices: Vec<usize>,
+    indptr: Vec<usize>,
+}
+
+impl CsrMatrix {
+    /// a matrix with `cols` columns and no rows.
+    pub fn new(cols: usize) -> Self {
+        CsrMatrix {
+            cols,
+            data: Vec::new(),
+            indices: Vec::new(),
+            indptr: vec![0],
+        }
+    }
+
+    /// `rows` as a matrix with at least `cols` columns, or more for entries
+    /// beyond.
+    pub fn from_rows(rows: &[SparseVec<f32>], cols: usize) -> Self {
+        let (data, indices, indptr) = to_csr(rows);
+        CsrMatrix {
+            cols: indices.iter().map(|&j| j + 1).fold(cols, usize::max),
+            data,
+            indices,
+            indptr,
+        }
+    }
+
+    /// checks that the arrays make a matrix with `cols` columns.
+    pub fn from_parts(data: Vec<f32>, indices: Vec<usize>, indptr: Vec<usize>, cols: usize)
+                      -> Result<Self, FormatError> {
+        if data.len() != indices.len() || indptr.first() != Some(&0) ||
+           indptr.last() != Some(&data.len()) || indptr.windows(2).any(|w| w[1] < w[0]) {
+            return Err(FormatError::new("inconsistent csr arrays"));
+        }
+        for w in indptr.windows(2) {
+            let row = &indices[w[0]..w[1]];
+            if row.windows(2).any(|js| js[1] <= js[0]) || row.last().is_some_and(|&j| cols <= j) {
+                return Err(FormatError::new("csr columns out of order or range"));
+            }
+        }
+        Ok(CsrMatrix {
+            cols,
+            data,
+            indices,
+            indptr,
+        })
+    }
+
+    /// appends `row`, whose dimensions must be below `cols`.
+    pub fn push_row(&mut self, row: &SparseVec<f32>) {
+        for &(j, x) in row {
+            assert!(j < self.cols, "column {} beyond {}", j, self.cols);
+            self.indices.push(j);
+            self.data.push(x);
+        }
+        self.indptr.push(self.data.len());
+    }
+
+    pub fn rows(&self) -> usize {
+        self.indptr.len() - 1
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// the number of stored entries.
+    pub fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn indptr(&self) -> &[usize] {
+        &self.indptr
+    }
+
+    /// the columns and values of row `i`.
+    pub fn row_entries(&self, i: usize) -> (&[usize], &[f32]) {
+        let (start, end) = (self.indptr[i], self.indptr[i + 1]);
+        (&self.indices[start..end], &self.data[start..end])
+    }
+
+    pub fn row(&self, i: usize) -> SparseVec<f32> {
+        let (indices, data) = self.row_entries(i);
+        indices.iter().cloned().zip(data.iter().cloned()).collect()
+    }
+
+    pub fn to_rows(&self) -> Vec<SparseVec<f32>> {
+        from_csr(&self.data, &self.indices, &self.indptr).unwrap()
+    }
+
+    /// returns the rows in `range`.
+    pub fn slice_rows(&self, range: Range<usize>) -> CsrMatrix {
+        let (start, end) = (self.indptr[range.start], self.indptr[range.end]);
+        CsrMatrix {
+            cols: self.cols,
+            data: self.data[start..end].to_vec(),
+            indices: self.indices[start..end].to_vec(),
+            indptr: self.indptr[range.start..range.end + 1].iter().map(|&n| n - start).collect(),
+        }
+    }
+
+    /// returns the rows numbered `rows`, in that order.
+    pub fn select_rows(&self, rows: &[usize]) -> CsrMatrix {
+        let mut m = CsrMatrix::new(self.cols);
+        for &i in rows {
+            let (indices, data) = self.row_entries(i);
+            m.indices.extend_from_slice(indices);
+            m.data.extend_from_slice(data);
+            m.indptr.push(m.data.len());
+        }
+        m
+    }
+
+    /// returns the columns in `range`, numbered from `range.start`.
+    pub fn slice_cols(&self, range: Range<usize>) -> CsrMatrix {
+        let mut m = CsrMatrix::new(range.len());
+        for i in 0..self.rows() {
+            let (indices, data) = self.row_entries(i);
+            for (&j, &x) in indices.iter().zip(data) {
+                if range.start <= j && j < range.end {
+                    m.indices.push(j - range.start);
+                    m.data.push(x);
+                }
+            }
+            m.indptr.push(m.data.len());
+        }
+        m
+    }
+
+    /// returns the columns numbered `cols`, which become the columns from `0`
+    /// in that order; a column may be taken more than once.
+    pub fn select_cols(&self, cols: &[usize]) -> CsrMatrix {
+        let mut old2new = vec![Vec::new(); self.cols];
+        for (new, &old) in cols.iter().enumerate() {
+            old2new[old].push(new)
+        }
+        let mut m = CsrMatrix::new(cols.len());
+        let mut row = Vec::new();
+        for i in 0..self.rows() {
+            let (indices, data) = self.row_entries(i);
+            for (&j, &x) in indices.iter().zip(data) {
+                row.extend(old2new[j].iter().map(|&new| (new, x)))
+            }
+            row.sort_by_key(|&(j, _)| j);
+            for (j, x) in row.drain(..) {
+                m.indices.push(j);
+                m.data.push(x);
+            }
+            m.indptr.push(m.data.len());
+        }
+        m
+    }
+
+    pub fn transpose(&self) -> CsrMatrix {
+        let mut indptr = vec![0; self.cols + 1];
+        for &j in &self.indices {
+            indptr[j + 1] += 1
+        }
+        for j in 0..self.cols {
+            indptr[j + 1] += indptr[j]
+        }
+        let mut next = indptr.clone();
+        let mut data = vec![0.0; self.nnz()];
+        let mut indices = vec![0; self.nnz()];
+        // the rows are visited in order, so the new rows come out sorted.
+        for i in 0..self.rows() {
+            let (cols, values) = self.row_entries(i);
+            for (&j, &x) in cols.iter().zip(values) {
+                data[next[j]] = x;
+                indices[next[j]] = i;
+                next[j] += 1
+            }
+        }
+        CsrMatrix {
+            cols: self.rows(),
+            data,
+            indices,
+            indptr,
+        }
+    }
+
+    /// returns the product with the dense vector `x`, of length `cols`.
+    pub fn mul_vec(&self, x: &[f32]) -> Vec<f32> {
+        assert_eq!(x.len(), self.cols, "a value for each column");
+        (0..self.rows())
+            .map(|i| {
+                let (indices, data) = self.row_entries(i);
+                indices.iter().zip(data).map(|(&j, &v)| v * x[j]).sum()
+            })
+            .collect()
+    }
+
+    /// returns the product with the dense matrix `b` of `cols` rows, each of
+    /// the same length.
+    pub fn mul_dense(&self, b: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        assert_eq!(b.len(), self.cols, "a row for each column");
+        let width = b.first().map_or(0, Vec::len);
+        (0..self.rows())
+            .map(|i| {
+                let mut row = vec![0.0; width];
+                let (indices, data) = self.row_entries(i);
+                for (&j, &v) in indices.iter().zip(data) {
+                    for (y, &u) in row.iter_mut().zip(&b[j]) {
+                        *y += v * u
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sv(entries: &[(usize, f32)]) -> SparseVec<f32> {
+        entries.iter().cloned().collect()
+    }
+
+    /// 3 x 4, with an empty row and an empty column.
+    fn matrix() -> CsrMatrix {
+        CsrMatrix::from_rows(&[sv(&[(0, 1.0), (3, 2.0)]), sv(&[]), sv(&[(1, 3.0), (3, 4.0)])], 4)
+    }
+
+    #[test]
+    fn transpose_twice_is_the_matrix() {
+        let m = matrix();
+        let t = m.transpose();
+        assert_eq!((t.rows(), t.cols()), (4, 3));
+        assert_eq!(t.to_rows(), [sv(&[(0, 1.0)]), sv(&[(2, 3.0)]), sv(&[]), sv(&[(0, 2.0), (2, 4.0)])]);
+        assert_eq!(t.transpose(), m);
+        assert_eq!(CsrMatrix::new(2).transpose().transpose(), CsrMatrix::new(2));
+    }
+
+    #[test]
+    fn slice_and_select_columns() {
+        let m = matrix();
+        let s = m.slice_cols(1..4);
+        assert_eq!(s.cols(), 3);
+        assert_eq!(s.to_rows(), [sv(&[(2, 2.0)]), sv(&[]), sv(&[(0, 3.0), (2, 4.0)])]);
+        assert_eq!(m.slice_cols(2..2).nnz(), 0);
+        let s = m.select_cols(&[3, 0, 3]);
+        assert_eq!(s.cols(), 3);
+        assert_eq!(s.to_rows(), [sv(&[(0, 2.0), (1, 1.0), (2, 2.0)]), sv(&[]), sv(&[(0, 4.0), (2, 4.0)])]);
+        assert_eq!(m.select_rows(&[2, 0]).to_rows(), [m.row(2), m.row(0)]);
+        assert_eq!(m.slice_rows(1..3).to_rows(), [m.row(1), m.row(2)]);
+    }
+
+    #[test]
+    fn products_with_dense() {
+        let m = matrix();
+        assert_eq!(m.mul_vec(&[1.0, 2.0, 3.0, 0.5]), [2.0, 0.0, 8.0]);
+        let b = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![5.0, 5.0], vec![1.0, -1.0]];
+        assert_eq!(m.mul_dense(&b), [vec![3.0, -2.0], vec![0.0, 0.0], vec![4.0, -1.0]]);
+    }
+
+    #[test]
+    fn from_parts_checks_the_arrays() {
+        let m = matrix();
+        let parts = || (m.data().to_vec(), m.indices().to_vec(), m.indptr().to_vec());
+        let (data, indices, indptr) = parts();
+        assert_eq!(CsrMatrix::from_parts(data, indices, indptr, 4).unwrap(), m);
+        let (data, indices, indptr) = parts();
+        assert!(CsrMatrix::from_parts(data, indices, indptr, 3).is_err());
+        let (data, _, indptr) = parts();
+        assert!(CsrMatrix::from_parts(data, vec![3, 0, 1, 3], indptr, 4).is_err());
+        let (data, indices, _) = parts();
+        assert!(CsrMatrix::from_parts(data, indices, vec![0, 2, 1, 4], 4).is_err());
+    }
+}
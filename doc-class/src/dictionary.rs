@@ -0,0 +1,116 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use vectorizer::Vectorizer;
+use error::{LoadError, FormatError};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+
+/// the names of the ngrams hashed into each term, as from
+/// `NgramExtractor::extract_named`; more than one name means a collision.
+pub type TermNames = HashMap<usize, BTreeSet<String>>;
+
+/// writes a line for each dimension of `vectorizer`, numbered from `0`, with
+/// the names of the ngrams hashed into its term, sorted and separated by
+/// tabs. the dimensions of the appended document vectors are named
+/// `embedding:K`.
+pub fn write_feature_dict<W>(wtr: &mut W, vectorizer: &Vectorizer, names: &TermNames)
+                             -> io::Result<()>
+    where W: Write
+{
+    let no_names = BTreeSet::new();
+    for (dim, term) in vectorizer.terms().iter().enumerate() {
+        write!(wtr, "{}", dim)?;
+        for name in names.get(term).unwrap_or(&no_names) {
+            write!(wtr, "\t{}", name)?;
+        }
+        writeln!(wtr)?;
+    }
+    let offset = vectorizer.terms().len();
+    for k in 0..vectorizer.dim() - offset {
+        writeln!(wtr, "{}\tembedding:{}", offset + k, k)?;
+    }
+    Ok(())
+}
+
+/// reads what `write_feature_dict` wrote, returning the names by dimension.
+pub fn read_feature_dict<R>(rdr: R) -> Result<Vec<Vec<String>>, LoadError>
+    where R: BufRead
+{
+    let mut dim2names = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        let mut fields = line.split('\t');
+        if fields.next().unwrap_or("").parse::<usize>()? != dim2names.len() {
+            return Err(FormatError::new(&line).into());
+        }
+        dim2names.push(fields.map(str::to_owned).collect());
+    }
+    Ok(dim2names)
+}
+
+/// returns the `k` dimensions with the largest weights, in descending order
+/// of weight, and in ascending order of dimension on ties.
+pub fn top_weights(weights: &[f32], k: usize) -> Vec<(usize, f32)> {
+    let mut dim2weight = weights.iter().cloned().enumerate().collect::<Vec<_>>();
+    dim2weight.sort_by(|&(dim1, w1), &(dim2, w2)| {
+        w2.partial_cmp(&w1).unwrap_or(Ordering::Equal).then(dim1.cmp(&dim2))
+    });
+    dim2weight.truncate(k);
+    dim2weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extraction::{Token, parse_specs};
+    use filters::Stopwords;
+    use vectorizer::document_terms;
+
+    #[test]
+    fn feature_dict_round_trip_with_collisions() {
+        let docs = ["cheap flights", "cheap pills", "new york flights"].iter()
+            .map(|doc| vec![doc.split(' ').map(|w| Token::new(w, w, "NN")).collect::<Vec<_>>()])
+            .collect::<Vec<_>>();
+        // five words in two dimensions.
+        let vectorizer = Vectorizer::builder()
+            .with_stopwords(Stopwords::NoFilter)
+            .with_specs(parse_specs("1:2^1").unwrap())
+            .fit(&docs, &["spam", "spam", "ham"]);
+        let mut names = TermNames::new();
+        for doc in &docs {
+            document_terms(vectorizer.stopwords(), vectorizer.extractor(), doc, true,
+                           Some(&mut names));
+        }
+        let expected = vectorizer.terms()
+            .iter()
+            .map(|term| names[term].iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(expected.iter().map(Vec::len).sum::<usize>(), 5);
+        assert!(expected.iter().any(|names| 1 < names.len()));
+
+        let mut buf = Vec::new();
+        write_feature_dict(&mut buf, &vectorizer, &names).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.lines().any(|line| 2 < line.split('\t').count()));
+        assert_eq!(read_feature_dict(text.as_bytes()).unwrap(), expected);
+    }
+
+    #[test]
+    fn read_feature_dict_checks_the_numbering() {
+        assert_eq!(read_feature_dict("0\ta\n1\n2\tb\tc\n".as_bytes()).unwrap(),
+                   vec![vec!["a".to_owned()], vec![], vec!["b".to_owned(), "c".to_owned()]]);
+        assert!(read_feature_dict("0\ta\n2\tb\n".as_bytes()).is_err());
+        assert!(read_feature_dict("a\t0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn top_weights_in_descending_order() {
+        let weights = [-0.5, 2.0, -3.0, 0.0, 2.0, -0.1];
+        assert_eq!(top_weights(&weights, 4), [(1, 2.0), (4, 2.0), (3, 0.0), (5, -0.1)]);
+        assert_eq!(top_weights(&weights, 10).last(), Some(&(2, -3.0)));
+        assert_eq!(top_weights(&weights, 10).len(), 6);
+        assert!(top_weights(&weights, 0).is_empty());
+    }
+}
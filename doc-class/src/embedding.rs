@@ -0,0 +1,300 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use rust2vec::{Embeddings, ReadWord2Vec};
+use error::LoadError;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+/// how the word vectors of a document are pooled into one vector.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Pooling {
+    Mean,
+    /// the mean weighted by tf-idf, with the document frequencies from the
+    /// fitted documents.
+    TfIdfMean,
+    /// the maximum of each component.
+    Max,
+}
+
+impl Pooling {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pooling::Mean => "mean",
+            Pooling::TfIdfMean => "tfidf",
+            Pooling::Max => "max",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Pooling> {
+        match name {
+            "mean" => Some(Pooling::Mean),
+            "tfidf" => Some(Pooling::TfIdfMean),
+            "max" => Some(Pooling::Max),
+            _ => None,
+        }
+    }
+}
+
+/// turns documents into dense unit vectors by pooling the normalized word2vec
+/// vectors of their tokens; tokens without vectors are ignored.
+#[derive(Clone)]
+pub struct DocEmbedder {
+    path: String,
+    pooling: Pooling,
+    embeddings: Arc<Embeddings>,
+    doc_count: usize,
+    word_dfs: HashMap<String, usize>,
+}
+
+impl fmt::Debug for DocEmbedder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DocEmbedder")
+            .field("path", &self.path)
+            .field("pooling", &self.pooling)
+            .field("dim", &self.dim())
+            .field("doc_count", &self.doc_count)
+            .finish()
+    }
+}
+
+/// embedders are equal when they read the same file and pool the same way.
+impl PartialEq for DocEmbedder {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.pooling == other.pooling &&
+        self.doc_count == other.doc_count && self.word_dfs == other.word_dfs
+    }
+}
+
+/// reads word2vec vectors in the binary format and scales them to unit length.
+pub fn read_embeddings(path: &str) -> Result<Embeddings, LoadError> {
+    let mut embeddings = Embeddings::read_word2vec_binary(&mut BufReader::new(File::open(path)?))?;
+    embeddings.normalize();
+    Ok(embeddings)
+}
+
+impl DocEmbedder {
+    /// `path` is where `embeddings` came from, kept for saving. the document
+    /// frequencies for `TfIdfMean` are counted over `docs`.
+    pub fn fit<I, D, S>(path: &str, embeddings: Arc<Embeddings>, pooling: Pooling, docs: I) -> Self
+        where I: IntoIterator<Item = D>,
+              D: AsRef<[S]>,
+              S: AsRef<str>
+    {
+        let mut doc_count = 0;
+        let mut word_dfs = HashMap::new();
+        for doc in docs {
+            doc_count += 1;
+            if pooling != Pooling::TfIdfMean {
+                continue;
+            }
+            let words = doc.as_ref()
+                .iter()
+                .map(AsRef::as_ref)
+                .filter(|word| embeddings.embedding(word).is_some())
+                .collect::<HashSet<&str>>();
+            for word in words {
+                *word_dfs.entry(word.to_owned()).or_insert(0) += 1
+            }
+        }
+        DocEmbedder::from_parts(path, embeddings, pooling, doc_count, word_dfs)
+    }
+
+    pub fn from_parts(path: &str,
+                      embeddings: Arc<Embeddings>,
+                      pooling: Pooling,
+                      doc_count: usize,
+                      word_dfs: HashMap<String, usize>)
+                      -> Self {
+        DocEmbedder {
+            path: path.to_owned(),
+            pooling,
+            embeddings,
+            doc_count,
+            word_dfs,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn pooling(&self) -> Pooling {
+        self.pooling
+    }
+
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    /// the document frequencies for `TfIdfMean`, empty otherwise.
+    pub fn word_dfs(&self) -> &HashMap<String, usize> {
+        &self.word_dfs
+    }
+
+    /// the length of the document vectors.
+    pub fn dim(&self) -> usize {
+        self.embeddings.embed_len()
+    }
+
+    /// returns the pooled vector of `tokens` scaled to unit length, or zeros
+    /// when no token has a vector.
+    pub fn embed<S>(&self, tokens: &[S]) -> Vec<f32>
+        where S: AsRef<str>
+    {
+        let mut tfs = HashMap::new();
+        for token in tokens {
+            *tfs.entry(token.as_ref()).or_insert(0) += 1
+        }
+        // sorted for summing in the same order every time.
+        let mut tfs = tfs.into_iter().collect::<Vec<_>>();
+        tfs.sort();
+        let mut pooled = vec![0.0; self.dim()];
+        let mut first = true;
+        for (word, tf) in tfs {
+            let embedding = match self.embeddings.embedding(word) {
+                Some(embedding) => embedding,
+                None => continue,
+            };
+            let weight = match self.pooling {
+                Pooling::Mean => tf as f32,
+                Pooling::TfIdfMean => {
+                    let df = self.word_dfs.get(word).cloned().unwrap_or(0);
+                    // smoothed, since unseen words have no document frequency.
+                    tf as f32 * (((1 + self.doc_count) as f32 / (1 + df) as f32).ln() + 1.0)
+                }
+                Pooling::Max => {
+                    for (p, &x) in pooled.iter_mut().zip(embedding.iter()) {
+                        if first || *p < x {
+                            *p = x
+                        }
+                    }
+                    first = false;
+                    continue;
+                }
+            };
+            // the sums need no division by the total weight, since the
+            // vector is scaled to unit length anyway.
+            for (p, &x) in pooled.iter_mut().zip(embedding.iter()) {
+                *p += weight * x
+            }
+        }
+        let norm = pooled.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if 0.0 < norm {
+            for p in &mut pooled {
+                *p /= norm
+            }
+        }
+        pooled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extraction::{Token, parse_specs};
+    use filters::Stopwords;
+    use rust2vec::ReadText;
+    use sparse_vec::{SparseVec, to_dense};
+    use std::io::Cursor;
+    use vectorizer::Vectorizer;
+    use weighting::Norm;
+
+    /// `a`, `b`, `c` and `d` normalized to `[1, 0]`, `[0, 1]`, `[0.6, 0.8]`
+    /// and `[-0.6, -0.8]`.
+    fn embeddings() -> Arc<Embeddings> {
+        let text = "a 2 0\nb 0 1\nc 3 4\nd -3 -4\n";
+        let mut embeddings = Embeddings::read_text(&mut Cursor::new(text)).unwrap();
+        embeddings.normalize();
+        Arc::new(embeddings)
+    }
+
+    fn embedder(pooling: Pooling) -> DocEmbedder {
+        let docs: &[&[&str]] = &[&["a", "b"], &["a"], &["a", "c", "a"]];
+        DocEmbedder::fit("", embeddings(), pooling, docs)
+    }
+
+    fn assert_close(x: &[f32], y: &[f32]) {
+        assert_eq!(x.len(), y.len());
+        for (a, b) in x.iter().zip(y) {
+            assert!((a - b).abs() < 1e-6, "{:?} != {:?}", x, y);
+        }
+    }
+
+    #[test]
+    fn mean_pooling_weighs_by_frequency() {
+        let embedder = embedder(Pooling::Mean);
+        assert_eq!(embedder.dim(), 2);
+        assert_eq!(embedder.doc_count(), 3);
+        assert!(embedder.word_dfs().is_empty());
+        let norm = f32::sqrt(5.0);
+        assert_close(&embedder.embed(&["b", "a", "b"]), &[1.0 / norm, 2.0 / norm]);
+        assert_close(&embedder.embed(&["a", "x", "c"]), &[2.0 / norm, 1.0 / norm]);
+        assert_close(&embedder.embed(&["c", "d"]), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn max_pooling_takes_the_largest_components() {
+        let embedder = embedder(Pooling::Max);
+        let norm = f32::sqrt(1.64);
+        assert_close(&embedder.embed(&["a", "c", "x"]), &[1.0 / norm, 0.8 / norm]);
+        // negative components are kept, and not raised to zero.
+        assert_close(&embedder.embed(&["d"]), &[-0.6, -0.8]);
+        assert_close(&embedder.embed(&["d", "d", "b"]), &[0.0, 1.0]);
+    }
+
+    #[test]
+    fn tf_idf_pooling_weighs_rare_words_more() {
+        let embedder = embedder(Pooling::TfIdfMean);
+        let dfs = [("a", 3), ("b", 1), ("c", 1)]
+            .iter()
+            .map(|&(word, df)| (word.to_owned(), df))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(embedder.word_dfs(), &dfs);
+        // `a` is in every document, and weighs `ln(4 / 4) + 1`.
+        let b = f32::ln(2.0) + 1.0;
+        let norm = f32::sqrt(1.0 + b * b);
+        assert_close(&embedder.embed(&["a", "b"]), &[1.0 / norm, b / norm]);
+        // unseen words have a document frequency of `0`.
+        let d = f32::ln(4.0) + 1.0;
+        let (x, y) = (-0.6 * d, b - 0.8 * d);
+        let norm = f32::sqrt(x * x + y * y);
+        assert_close(&embedder.embed(&["b", "d"]), &[x / norm, y / norm]);
+    }
+
+    #[test]
+    fn documents_without_vectors_embed_to_zeros() {
+        for &pooling in &[Pooling::Mean, Pooling::TfIdfMean, Pooling::Max] {
+            let embedder = embedder(pooling);
+            assert_eq!(embedder.embed(&["x", "y", "x"]), [0.0, 0.0]);
+            assert_eq!(embedder.embed::<&str>(&[]), [0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn embedding_follows_the_terms() {
+        let docs = ["a b", "a c", "x y"].iter()
+            .map(|doc| vec![doc.split(' ').map(|w| Token::new(w, w, "NN")).collect::<Vec<_>>()])
+            .collect::<Vec<_>>();
+        let vectorizer = Vectorizer::builder()
+            .with_stopwords(Stopwords::NoFilter)
+            .with_specs(parse_specs("1:2^4").unwrap())
+            .with_norm(Norm::NoNorm)
+            .fit(&docs, &["0", "0", "1"])
+            .with_embedder(embedder(Pooling::Mean));
+        let offset = vectorizer.terms().len();
+        assert_eq!(vectorizer.dim(), offset + 2);
+        let feat_vec = vectorizer.transform(&docs[0]);
+        let embedded = vectorizer.append_embedding(SparseVec::new(), &["a", "b"]);
+        assert_eq!(feat_vec.iter().filter(|&&(dim, _)| offset <= dim).count(), 2);
+        let dense = to_dense(&feat_vec, vectorizer.dim());
+        assert_eq!(&dense[offset..], &to_dense(&embedded, vectorizer.dim())[offset..]);
+        assert_close(&dense[offset..], &[f32::sqrt(0.5), f32::sqrt(0.5)]);
+        // the ngrams `x` and `y` have no vectors.
+        let feat_vec = vectorizer.transform(&docs[2]);
+        assert!(feat_vec.iter().all(|&(dim, _)| dim < offset));
+    }
+}
@@ -0,0 +1,88 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use std::error;
+use std::fmt;
+use std::num;
+use std::io;
+use rust2vec;
+
+#[derive(Debug)]
+pub struct FormatError(String);
+
+impl FormatError {
+    pub fn new(trouble: &str) -> Self {
+        FormatError(trouble.to_owned())
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "illformed: {}", self.0)
+    }
+}
+
+impl error::Error for FormatError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    ParseInt(num::ParseIntError),
+    ParseFloat(num::ParseFloatError),
+    Format(FormatError),
+    Embeddings(rust2vec::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "IO error: {}", err),
+            LoadError::ParseInt(ref err) => write!(f, "Parse error: {}", err),
+            LoadError::ParseFloat(ref err) => write!(f, "Parse error: {}", err),
+            LoadError::Format(ref err) => write!(f, "Format error: {}", err),
+            LoadError::Embeddings(ref err) => write!(f, "Embeddings error: {}", err),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            LoadError::Io(ref err) => Some(err),
+            LoadError::ParseInt(ref err) => Some(err),
+            LoadError::ParseFloat(ref err) => Some(err),
+            LoadError::Format(ref err) => Some(err),
+            LoadError::Embeddings(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<num::ParseIntError> for LoadError {
+    fn from(err: num::ParseIntError) -> LoadError {
+        LoadError::ParseInt(err)
+    }
+}
+
+impl From<num::ParseFloatError> for LoadError {
+    fn from(err: num::ParseFloatError) -> LoadError {
+        LoadError::ParseFloat(err)
+    }
+}
+
+impl From<FormatError> for LoadError {
+    fn from(err: FormatError) -> LoadError {
+        LoadError::Format(err)
+    }
+}
+
+impl From<rust2vec::Error> for LoadError {
+    fn from(err: rust2vec::Error) -> LoadError {
+        LoadError::Embeddings(err)
+    }
+}
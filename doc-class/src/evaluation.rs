@@ -0,0 +1,759 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use error::FormatError;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// counts of `(gold, predicted)` class pairs, with classes kept by name in the
+/// order of their first appearance.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+pub struct ConfusionMatrix {
+    outcomes: ClassOutcomes,
+    counts: Vec<Vec<usize>>,
+}
+
+/// precision, recall, and f1 of one class, or averaged over classes.
+#[derive(Debug,Default,Clone,Copy,PartialEq)]
+pub struct Scores {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl Scores {
+    /// computes the scores from true positives, false positives, and false
+    /// negatives; undefined ratios are taken as `0`.
+    pub fn from_counts(tp: usize, fp: usize, fn_: usize) -> Self {
+        let precision = ratio(tp, tp + fp);
+        let recall = ratio(tp, tp + fn_);
+        Scores {
+            precision,
+            recall,
+            f1: harmonic_mean(precision, recall),
+        }
+    }
+}
+
+/// the outcomes of each class as a binary decision, with classes kept by name
+/// in the order of their first appearance, shared by `ConfusionMatrix` and
+/// `MultiLabelEvaluation`.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+struct ClassOutcomes {
+    classes: Vec<String>,
+    class2idx: HashMap<String, usize>,
+    /// `(true positives, false positives, false negatives)` for each class.
+    outcomes: Vec<(usize, usize, usize)>,
+}
+
+impl ClassOutcomes {
+    fn class_index(&mut self, class: &str) -> usize {
+        if let Some(&k) = self.class2idx.get(class) {
+            return k;
+        }
+        let k = self.classes.len();
+        self.classes.push(class.to_owned());
+        self.class2idx.insert(class.to_owned(), k);
+        self.outcomes.push((0, 0, 0));
+        k
+    }
+
+    fn support(&self, k: usize) -> usize {
+        let (tp, _, fn_) = self.outcomes[k];
+        tp + fn_
+    }
+
+    fn class_scores(&self, k: usize) -> Scores {
+        let (tp, fp, fn_) = self.outcomes[k];
+        Scores::from_counts(tp, fp, fn_)
+    }
+
+    fn macro_scores(&self) -> Scores {
+        let n = self.classes.len() as f32;
+        let mut avg = Scores::default();
+        for k in 0..self.classes.len() {
+            let scores = self.class_scores(k);
+            avg.precision += scores.precision / n;
+            avg.recall += scores.recall / n;
+            avg.f1 += scores.f1 / n;
+        }
+        avg
+    }
+
+    fn micro_scores(&self) -> Scores {
+        let (mut tp, mut fp, mut fn_) = (0, 0, 0);
+        for &(t, p, n) in &self.outcomes {
+            tp += t;
+            fp += p;
+            fn_ += n;
+        }
+        Scores::from_counts(tp, fp, fn_)
+    }
+
+    /// the width of the class column in the reports.
+    fn width(&self) -> usize {
+        self.classes.iter().map(String::len).max().unwrap_or(0).max(8)
+    }
+
+    /// writes the scores of each class, followed by the `averages` over
+    /// `total` documents, in columns.
+    fn write_report<W>(&self, wtr: &mut W, averages: &[(&str, Scores)], total: usize) -> io::Result<()>
+        where W: Write
+    {
+        let width = self.width();
+        writeln!(wtr, "{:<width$} {:>9} {:>9} {:>9} {:>9}",
+                 "class", "precision", "recall", "f1", "support", width = width)?;
+        for (k, class) in self.classes.iter().enumerate() {
+            let scores = self.class_scores(k);
+            writeln!(wtr, "{:<width$} {:>9.4} {:>9.4} {:>9.4} {:>9}",
+                     class, scores.precision, scores.recall, scores.f1, self.support(k),
+                     width = width)?;
+        }
+        for &(name, scores) in averages {
+            writeln!(wtr, "{:<width$} {:>9.4} {:>9.4} {:>9.4} {:>9}",
+                     name, scores.precision, scores.recall, scores.f1, total, width = width)?;
+        }
+        Ok(())
+    }
+
+    /// as `write_report`, separated by tabs.
+    fn write_tsv<W>(&self, wtr: &mut W, averages: &[(&str, Scores)], total: usize) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "class\tprecision\trecall\tf1\tsupport")?;
+        for (k, class) in self.classes.iter().enumerate() {
+            let scores = self.class_scores(k);
+            writeln!(wtr, "{}\t{}\t{}\t{}\t{}",
+                     class, scores.precision, scores.recall, scores.f1, self.support(k))?;
+        }
+        for &(name, scores) in averages {
+            writeln!(wtr, "{}\t{}\t{}\t{}\t{}",
+                     name, scores.precision, scores.recall, scores.f1, total)?;
+        }
+        Ok(())
+    }
+
+    /// writes the json field `classes` with the scores of each class,
+    /// followed by a comma unless it is the `last` field.
+    fn write_json<W>(&self, wtr: &mut W, last: bool) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "  \"classes\": [")?;
+        for (k, class) in self.classes.iter().enumerate() {
+            let scores = self.class_scores(k);
+            writeln!(wtr,
+                     "    {{\"class\": {}, \"precision\": {}, \"recall\": {}, \"f1\": {}, \
+                      \"support\": {}}}{}",
+                     json_string(class), scores.precision, scores.recall, scores.f1,
+                     self.support(k), if k + 1 < self.classes.len() { "," } else { "" })?;
+        }
+        writeln!(wtr, "  ]{}", if last { "" } else { "," })
+    }
+}
+
+impl ConfusionMatrix {
+    pub fn new() -> Self {
+        ConfusionMatrix::default()
+    }
+
+    /// builds a matrix from aligned gold and predicted labels.
+    pub fn from_labels<S>(gold: &[S], pred: &[S]) -> Self
+        where S: AsRef<str>
+    {
+        let mut matrix = ConfusionMatrix::new();
+        for (g, p) in gold.iter().zip(pred.iter()) {
+            matrix.add(g.as_ref(), p.as_ref())
+        }
+        matrix
+    }
+
+    fn class_index(&mut self, class: &str) -> usize {
+        let k = self.outcomes.class_index(class);
+        if k == self.counts.len() {
+            for row in &mut self.counts {
+                row.push(0)
+            }
+            self.counts.push(vec![0; k + 1]);
+        }
+        k
+    }
+
+    pub fn add(&mut self, gold: &str, pred: &str) {
+        let g = self.class_index(gold);
+        let p = self.class_index(pred);
+        self.counts[g][p] += 1;
+        if g == p {
+            self.outcomes.outcomes[g].0 += 1
+        } else {
+            self.outcomes.outcomes[p].1 += 1;
+            self.outcomes.outcomes[g].2 += 1
+        }
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.outcomes.classes
+    }
+
+    /// the number of documents of class `gold` predicted as class `pred`, both
+    /// by index.
+    pub fn count(&self, gold: usize, pred: usize) -> usize {
+        self.counts[gold][pred]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|row| row.iter().sum::<usize>()).sum()
+    }
+
+    pub fn correct(&self) -> usize {
+        (0..self.counts.len()).map(|k| self.counts[k][k]).sum()
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        ratio(self.correct(), self.total())
+    }
+
+    /// the number of gold documents of class `k`.
+    pub fn support(&self, k: usize) -> usize {
+        self.outcomes.support(k)
+    }
+
+    /// returns `(true positives, false positives, false negatives)` for class `k`.
+    pub fn outcomes(&self, k: usize) -> (usize, usize, usize) {
+        self.outcomes.outcomes[k]
+    }
+
+    pub fn class_scores(&self, k: usize) -> Scores {
+        self.outcomes.class_scores(k)
+    }
+
+    /// the unweighted mean of the scores over all classes.
+    pub fn macro_scores(&self) -> Scores {
+        self.outcomes.macro_scores()
+    }
+
+    /// the scores over the outcomes pooled from all classes.
+    pub fn micro_scores(&self) -> Scores {
+        self.outcomes.micro_scores()
+    }
+
+    fn averages(&self) -> [(&'static str, Scores); 2] {
+        [("macro", self.macro_scores()), ("micro", self.micro_scores())]
+    }
+}
+
+/// ways of writing out evaluation results.
+pub trait Report {
+    /// writes a human-readable report.
+    fn write_report<W>(&self, wtr: &mut W) -> io::Result<()> where W: Write;
+
+    fn write_tsv<W>(&self, wtr: &mut W) -> io::Result<()> where W: Write;
+
+    fn write_json<W>(&self, wtr: &mut W) -> io::Result<()> where W: Write;
+}
+
+impl Report for ConfusionMatrix {
+    /// writes the confusion matrix, with gold classes in rows and predicted
+    /// classes in columns, followed by the scores.
+    fn write_report<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        let classes = self.classes();
+        let width = self.outcomes.width();
+        let cell = self.total().to_string().len().max(classes.len().to_string().len()) + 1;
+        write!(wtr, "{:>w$}", "", w = width + cell + 1)?;
+        for k in 0..classes.len() {
+            write!(wtr, "{:>w$}", k, w = cell)?;
+        }
+        writeln!(wtr)?;
+        for (g, class) in classes.iter().enumerate() {
+            write!(wtr, "{:>w$} {:<width$}", g, class, w = cell, width = width)?;
+            for p in 0..classes.len() {
+                write!(wtr, "{:>w$}", self.counts[g][p], w = cell)?;
+            }
+            writeln!(wtr)?;
+        }
+        writeln!(wtr)?;
+        self.outcomes.write_report(wtr, &self.averages(), self.total())?;
+        writeln!(wtr, "accuracy = {}% ({}/{})",
+                 100.0 * self.accuracy(), self.correct(), self.total())
+    }
+
+    /// writes the scores as a tab-separated table, followed by an empty line
+    /// and the confusion matrix.
+    fn write_tsv<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        self.outcomes.write_tsv(wtr, &self.averages(), self.total())?;
+        writeln!(wtr, "accuracy\t\t\t{}\t{}", self.accuracy(), self.total())?;
+        writeln!(wtr)?;
+        write!(wtr, "gold\\pred")?;
+        for class in self.classes() {
+            write!(wtr, "\t{}", class)?;
+        }
+        writeln!(wtr)?;
+        for (g, class) in self.classes().iter().enumerate() {
+            write!(wtr, "{}", class)?;
+            for n in &self.counts[g] {
+                write!(wtr, "\t{}", n)?;
+            }
+            writeln!(wtr)?;
+        }
+        Ok(())
+    }
+
+    fn write_json<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "{{")?;
+        writeln!(wtr, "  \"accuracy\": {},", self.accuracy())?;
+        writeln!(wtr, "  \"total\": {},", self.total())?;
+        writeln!(wtr, "  \"macro\": {},", json_scores(self.macro_scores()))?;
+        writeln!(wtr, "  \"micro\": {},", json_scores(self.micro_scores()))?;
+        self.outcomes.write_json(wtr, false)?;
+        writeln!(wtr, "  \"confusion\": [")?;
+        for (g, row) in self.counts.iter().enumerate() {
+            let row = row.iter().map(ToString::to_string).collect::<Vec<_>>();
+            writeln!(wtr, "    [{}]{}",
+                     row.join(", "), if g + 1 < self.counts.len() { "," } else { "" })?;
+        }
+        writeln!(wtr, "  ]")?;
+        writeln!(wtr, "}}")
+    }
+}
+
+/// scores for documents with sets of labels, where classes are kept by name
+/// in the order of their first appearance. the example-based scores compare
+/// the gold and predicted sets of each document and are averaged over the
+/// documents; the label-based scores treat each class as a binary decision.
+#[derive(Debug,Default,Clone,PartialEq)]
+pub struct MultiLabelEvaluation {
+    outcomes: ClassOutcomes,
+    examples: usize,
+    exact_matches: usize,
+    /// the sum of the sizes of the symmetric differences.
+    mismatches: usize,
+    example_sums: Scores,
+    jaccard_sum: f32,
+}
+
+impl MultiLabelEvaluation {
+    pub fn new() -> Self {
+        MultiLabelEvaluation::default()
+    }
+
+    /// builds an evaluation from aligned gold and predicted label sets.
+    pub fn from_label_sets<S>(gold: &[Vec<S>], pred: &[Vec<S>]) -> Self
+        where S: AsRef<str>
+    {
+        let mut evaluation = MultiLabelEvaluation::new();
+        for (g, p) in gold.iter().zip(pred.iter()) {
+            evaluation.add(g, p)
+        }
+        evaluation
+    }
+
+    /// adds a document with `gold` and `pred` labels; repeated labels count
+    /// once.
+    pub fn add<S>(&mut self, gold: &[S], pred: &[S])
+        where S: AsRef<str>
+    {
+        let table = &mut self.outcomes;
+        let mut gold = gold.iter().map(|class| table.class_index(class.as_ref())).collect::<Vec<_>>();
+        let mut pred = pred.iter().map(|class| table.class_index(class.as_ref())).collect::<Vec<_>>();
+        gold.sort();
+        gold.dedup();
+        pred.sort();
+        pred.dedup();
+        let mut both = 0;
+        for &k in &gold {
+            if pred.contains(&k) {
+                both += 1;
+                table.outcomes[k].0 += 1
+            } else {
+                table.outcomes[k].2 += 1
+            }
+        }
+        for &k in &pred {
+            if !gold.contains(&k) {
+                table.outcomes[k].1 += 1
+            }
+        }
+        let union = gold.len() + pred.len() - both;
+        self.examples += 1;
+        self.mismatches += union - both;
+        if union == both {
+            // also when both sets are empty, which counts as perfect.
+            self.exact_matches += 1;
+            self.example_sums.precision += 1.0;
+            self.example_sums.recall += 1.0;
+            self.example_sums.f1 += 1.0;
+            self.jaccard_sum += 1.0;
+        } else {
+            self.example_sums.precision += ratio(both, pred.len());
+            self.example_sums.recall += ratio(both, gold.len());
+            self.example_sums.f1 += ratio(2 * both, gold.len() + pred.len());
+            self.jaccard_sum += ratio(both, union);
+        }
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.outcomes.classes
+    }
+
+    /// the number of documents.
+    pub fn total(&self) -> usize {
+        self.examples
+    }
+
+    /// the number of documents whose predicted set equals the gold set.
+    pub fn exact_matches(&self) -> usize {
+        self.exact_matches
+    }
+
+    /// the fraction of documents whose predicted set equals the gold set.
+    pub fn subset_accuracy(&self) -> f32 {
+        ratio(self.exact_matches, self.examples)
+    }
+
+    /// the fraction of wrong binary decisions over all documents and classes.
+    pub fn hamming_loss(&self) -> f32 {
+        ratio(self.mismatches, self.examples * self.classes().len())
+    }
+
+    /// the mean of `|gold & pred| / |gold | pred|` over the documents.
+    pub fn jaccard(&self) -> f32 {
+        if self.examples == 0 { 0.0 } else { self.jaccard_sum / self.examples as f32 }
+    }
+
+    /// the mean of the scores of the sets over the documents.
+    pub fn example_scores(&self) -> Scores {
+        let n = self.examples as f32;
+        if self.examples == 0 {
+            return Scores::default();
+        }
+        Scores {
+            precision: self.example_sums.precision / n,
+            recall: self.example_sums.recall / n,
+            f1: self.example_sums.f1 / n,
+        }
+    }
+
+    /// the number of gold documents of class `k`.
+    pub fn support(&self, k: usize) -> usize {
+        self.outcomes.support(k)
+    }
+
+    /// returns `(true positives, false positives, false negatives)` for class `k`.
+    pub fn outcomes(&self, k: usize) -> (usize, usize, usize) {
+        self.outcomes.outcomes[k]
+    }
+
+    pub fn class_scores(&self, k: usize) -> Scores {
+        self.outcomes.class_scores(k)
+    }
+
+    /// the unweighted mean of the scores over all classes.
+    pub fn macro_scores(&self) -> Scores {
+        self.outcomes.macro_scores()
+    }
+
+    /// the scores over the outcomes pooled from all classes.
+    pub fn micro_scores(&self) -> Scores {
+        self.outcomes.micro_scores()
+    }
+
+    fn averages(&self) -> [(&'static str, Scores); 3] {
+        [("macro", self.macro_scores()),
+         ("micro", self.micro_scores()),
+         ("example", self.example_scores())]
+    }
+}
+
+impl Report for MultiLabelEvaluation {
+    /// writes the label-based scores of each class, followed by their
+    /// averages and the example-based scores.
+    fn write_report<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        self.outcomes.write_report(wtr, &self.averages(), self.total())?;
+        writeln!(wtr, "jaccard = {}", self.jaccard())?;
+        writeln!(wtr, "hamming loss = {}", self.hamming_loss())?;
+        writeln!(wtr, "subset accuracy = {}% ({}/{})",
+                 100.0 * self.subset_accuracy(), self.exact_matches(), self.total())
+    }
+
+    /// writes the scores as a tab-separated table.
+    fn write_tsv<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        self.outcomes.write_tsv(wtr, &self.averages(), self.total())?;
+        writeln!(wtr, "jaccard\t\t\t{}\t{}", self.jaccard(), self.total())?;
+        writeln!(wtr, "hamming_loss\t\t\t{}\t{}", self.hamming_loss(), self.total())?;
+        writeln!(wtr, "subset_accuracy\t\t\t{}\t{}", self.subset_accuracy(), self.total())
+    }
+
+    fn write_json<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "{{")?;
+        writeln!(wtr, "  \"subset_accuracy\": {},", self.subset_accuracy())?;
+        writeln!(wtr, "  \"hamming_loss\": {},", self.hamming_loss())?;
+        writeln!(wtr, "  \"jaccard\": {},", self.jaccard())?;
+        writeln!(wtr, "  \"total\": {},", self.total())?;
+        writeln!(wtr, "  \"example\": {},", json_scores(self.example_scores()))?;
+        writeln!(wtr, "  \"macro\": {},", json_scores(self.macro_scores()))?;
+        writeln!(wtr, "  \"micro\": {},", json_scores(self.micro_scores()))?;
+        self.outcomes.write_json(wtr, true)?;
+        writeln!(wtr, "}}")
+    }
+}
+
+/// reads one label per line, skipping blank lines; when a line has several
+/// tab-separated fields, as written by `doc-class predict`, the last one is
+/// the label, and the ones before it the path of the document.
+pub fn read_labels<R>(rdr: R) -> io::Result<Vec<(Option<String>, String)>>
+    where R: BufRead
+{
+    let mut labels = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        labels.push(match line.rfind('\t') {
+            Some(x) => (Some(line[..x].to_owned()), line[x + 1..].trim().to_owned()),
+            None => (None, line.trim().to_owned()),
+        })
+    }
+    Ok(labels)
+}
+
+/// reads one set of comma-separated labels per line, where the last
+/// tab-separated field holds the labels, as for `read_labels`.
+pub fn read_label_sets<R>(rdr: R) -> io::Result<Vec<(Option<String>, Vec<String>)>>
+    where R: BufRead
+{
+    Ok(read_labels(rdr)?.into_iter().map(|(path, labels)| (path, split_labels(&labels))).collect())
+}
+
+/// pairs the `gold` labels with the `pred`icted ones, by the paths of the
+/// documents when both have them, and otherwise line by line. returns the
+/// labels in the order of `gold`, or an error when the counts differ or the
+/// paths do not match up.
+pub fn pair_labels<T>(gold: Vec<(Option<String>, T)>,
+                      pred: Vec<(Option<String>, T)>)
+                      -> Result<(Vec<T>, Vec<T>), FormatError> {
+    if gold.len() != pred.len() {
+        return Err(FormatError::new(&format!("{} gold labels but {} predicted labels",
+                                             gold.len(), pred.len())));
+    }
+    let with_paths = |labels: &[(Option<String>, T)]| labels.iter().all(|(path, _)| path.is_some());
+    if !with_paths(&gold) || !with_paths(&pred) {
+        let gold = gold.into_iter().map(|(_, label)| label);
+        return Ok(gold.zip(pred.into_iter().map(|(_, label)| label)).unzip());
+    }
+    let mut path2pred = HashMap::new();
+    for (path, label) in pred {
+        let path = path.unwrap();
+        if path2pred.contains_key(&path) {
+            return Err(FormatError::new(&format!("repeated prediction for {}", path)));
+        }
+        path2pred.insert(path, label);
+    }
+    let mut pairs = (Vec::new(), Vec::new());
+    for (path, label) in gold {
+        let path = path.unwrap();
+        match path2pred.remove(&path) {
+            Some(pred) => {
+                pairs.0.push(label);
+                pairs.1.push(pred)
+            }
+            None => {
+                return Err(FormatError::new(&format!("no prediction for {}, or a repeated one",
+                                                     path)))
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// splits comma-separated labels, dropping empty ones.
+pub fn split_labels(labels: &str) -> Vec<String> {
+    labels.split(',').map(str::trim).filter(|label| !label.is_empty()).map(str::to_owned).collect()
+}
+
+fn json_scores(scores: Scores) -> String {
+    format!("{{\"precision\": {}, \"recall\": {}, \"f1\": {}}}",
+            scores.precision, scores.recall, scores.f1)
+}
+
+fn ratio(n: usize, d: usize) -> f32 {
+    if d == 0 { 0.0 } else { n as f32 / d as f32 }
+}
+
+fn harmonic_mean(x: f32, y: f32) -> f32 {
+    if x + y == 0.0 { 0.0 } else { 2.0 * x * y / (x + y) }
+}
+
+/// quotes and escapes `s` as a json string.
+pub fn json_string(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_scores(scores: Scores, precision: f32, recall: f32, f1: f32) {
+        assert!((scores.precision - precision).abs() < 1e-6, "{:?}", scores);
+        assert!((scores.recall - recall).abs() < 1e-6, "{:?}", scores);
+        assert!((scores.f1 - f1).abs() < 1e-6, "{:?}", scores);
+    }
+
+    /// `a` has 2 tp, 0 fp, 1 fn; `b` 1 tp, 1 fp, 1 fn; `d` is only predicted,
+    /// with 1 fp; `c` 1 tp.
+    fn matrix() -> ConfusionMatrix {
+        ConfusionMatrix::from_labels(&["a", "a", "a", "b", "b", "c"],
+                                     &["a", "a", "b", "b", "d", "c"])
+    }
+
+    #[test]
+    fn confusion_matrix_scores() {
+        let matrix = matrix();
+        assert_eq!(matrix.classes(), ["a", "b", "d", "c"]);
+        assert_eq!(matrix.count(0, 1), 1);
+        assert_eq!(matrix.outcomes(0), (2, 0, 1));
+        assert_eq!(matrix.outcomes(1), (1, 1, 1));
+        assert_eq!(matrix.outcomes(2), (0, 1, 0));
+        assert_eq!(matrix.support(2), 0);
+        assert_scores(matrix.class_scores(0), 1.0, 2.0 / 3.0, 0.8);
+        assert_scores(matrix.class_scores(1), 0.5, 0.5, 0.5);
+        // no gold documents, so the recall is taken as `0`.
+        assert_scores(matrix.class_scores(2), 0.0, 0.0, 0.0);
+        assert_scores(matrix.class_scores(3), 1.0, 1.0, 1.0);
+        assert_scores(matrix.macro_scores(), 2.5 / 4.0, (2.0 / 3.0 + 1.5) / 4.0, 2.3 / 4.0);
+        assert_scores(matrix.micro_scores(), 4.0 / 6.0, 4.0 / 6.0, 4.0 / 6.0);
+        assert_eq!(matrix.correct(), 4);
+        assert_eq!(matrix.total(), 6);
+        assert_eq!(matrix.accuracy(), 4.0 / 6.0);
+        let empty = ConfusionMatrix::new();
+        assert_eq!(empty.accuracy(), 0.0);
+        assert_eq!(empty.micro_scores(), Scores::default());
+    }
+
+    #[test]
+    fn confusion_matrix_tsv_and_json() {
+        let mut tsv = Vec::new();
+        matrix().write_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "class\tprecision\trecall\tf1\tsupport");
+        assert_eq!(lines[2], "b\t0.5\t0.5\t0.5\t2");
+        assert_eq!(lines[3], "d\t0\t0\t0\t0");
+        assert_eq!(lines[4], "c\t1\t1\t1\t1");
+        assert_eq!(lines[5], "macro\t0.625\t0.5416667\t0.575\t6");
+        assert_eq!(lines[7], format!("accuracy\t\t\t{}\t6", 4.0f32 / 6.0));
+        assert_eq!(lines[8..],
+                   ["", "gold\\pred\ta\tb\td\tc", "a\t2\t1\t0\t0", "b\t0\t1\t1\t0",
+                    "d\t0\t0\t0\t0", "c\t0\t0\t0\t1"]);
+        let mut json = Vec::new();
+        matrix().write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        let lines = json.lines().map(str::trim).collect::<Vec<_>>();
+        assert_eq!(lines[1], format!("\"accuracy\": {},", 4.0f32 / 6.0));
+        assert_eq!(lines[2], "\"total\": 6,");
+        assert_eq!(lines[3], "\"macro\": {\"precision\": 0.625, \"recall\": 0.5416667, \"f1\": 0.575},");
+        assert_eq!(lines[8],
+                   "{\"class\": \"d\", \"precision\": 0, \"recall\": 0, \"f1\": 0, \"support\": 0},");
+        assert_eq!(lines[9],
+                   "{\"class\": \"c\", \"precision\": 1, \"recall\": 1, \"f1\": 1, \"support\": 1}");
+        assert_eq!(lines[12..], ["[2, 1, 0, 0],", "[0, 1, 1, 0],", "[0, 0, 0, 0],", "[0, 0, 0, 1]",
+                                 "]", "}"]);
+    }
+
+    #[test]
+    fn multi_label_scores() {
+        let gold = vec![vec!["a", "b"], vec!["c"], vec![], vec!["a"]];
+        let pred = vec![vec!["a"], vec!["c", "c"], vec!["b"], vec!["b", "c"]];
+        let evaluation = MultiLabelEvaluation::from_label_sets(&gold, &pred);
+        assert_eq!(evaluation.classes(), ["a", "b", "c"]);
+        assert_eq!(evaluation.total(), 4);
+        assert_eq!(evaluation.exact_matches(), 1);
+        assert_eq!(evaluation.subset_accuracy(), 0.25);
+        // 1 + 0 + 1 + 3 wrong decisions out of 4 * 3.
+        assert_eq!(evaluation.hamming_loss(), 5.0 / 12.0);
+        assert_eq!(evaluation.jaccard(), 1.5 / 4.0);
+        assert_scores(evaluation.example_scores(), 2.0 / 4.0, 1.5 / 4.0, (2.0 / 3.0 + 1.0) / 4.0);
+        assert_eq!(evaluation.outcomes(0), (1, 0, 1));
+        assert_eq!(evaluation.outcomes(1), (0, 2, 1));
+        assert_eq!(evaluation.outcomes(2), (1, 1, 0));
+        assert_eq!(evaluation.support(1), 1);
+        assert_scores(evaluation.class_scores(0), 1.0, 0.5, 2.0 / 3.0);
+        assert_scores(evaluation.class_scores(1), 0.0, 0.0, 0.0);
+        assert_scores(evaluation.class_scores(2), 0.5, 1.0, 2.0 / 3.0);
+        assert_scores(evaluation.macro_scores(), 0.5, 0.5, 4.0 / 9.0);
+        assert_scores(evaluation.micro_scores(), 0.4, 0.5, 4.0 / 9.0);
+        let mut tsv = Vec::new();
+        evaluation.write_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        assert_eq!(tsv.lines().nth(2), Some("b\t0\t0\t0\t1"));
+        assert_eq!(tsv.lines().last(), Some("subset_accuracy\t\t\t0.25\t4"));
+    }
+
+    #[test]
+    fn empty_multi_label_evaluation_scores_zero() {
+        let evaluation = MultiLabelEvaluation::new();
+        assert_eq!(evaluation.subset_accuracy(), 0.0);
+        assert_eq!(evaluation.hamming_loss(), 0.0);
+        assert_eq!(evaluation.jaccard(), 0.0);
+        assert_eq!(evaluation.example_scores(), Scores::default());
+        // two empty sets match exactly.
+        let evaluation = MultiLabelEvaluation::from_label_sets::<&str>(&[vec![]], &[vec![]]);
+        assert_eq!(evaluation.subset_accuracy(), 1.0);
+        assert_eq!(evaluation.jaccard(), 1.0);
+    }
+
+    #[test]
+    fn read_labels_skips_blank_lines() {
+        let labels = read_labels("a\n\nb\t c\n\n".as_bytes()).unwrap();
+        assert_eq!(labels, [(None, "a".to_owned()), (Some("b".to_owned()), "c".to_owned())]);
+    }
+
+    #[test]
+    fn pair_labels_joins_on_paths() {
+        let gold = read_labels("x\ta\ny\tb\n".as_bytes()).unwrap();
+        let pred = read_labels("y\tc\nx\ta\n".as_bytes()).unwrap();
+        let (gold, pred) = pair_labels(gold, pred).unwrap();
+        assert_eq!(gold, ["a", "b"]);
+        assert_eq!(pred, ["a", "c"]);
+    }
+
+    #[test]
+    fn pair_labels_falls_back_to_lines() {
+        let gold = read_labels("a\nb\n".as_bytes()).unwrap();
+        let pred = read_labels("y\tc\nx\ta\n".as_bytes()).unwrap();
+        assert_eq!(pair_labels(gold, pred).unwrap(),
+                   (vec!["a".to_owned(), "b".to_owned()], vec!["c".to_owned(), "a".to_owned()]));
+    }
+
+    #[test]
+    fn pair_labels_rejects_mismatches() {
+        let gold = || read_labels("x\ta\ny\tb\n".as_bytes()).unwrap();
+        assert!(pair_labels(gold(), read_labels("x\ta\n".as_bytes()).unwrap()).is_err());
+        assert!(pair_labels(gold(), read_labels("x\ta\nz\tb\n".as_bytes()).unwrap()).is_err());
+        assert!(pair_labels(gold(), read_labels("x\ta\nx\tb\n".as_bytes()).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,591 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use hashing::FeatureHasher;
+use error::{LoadError, FormatError};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+
+/// what a token stands for in the ngrams.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum TokenUnit {
+    Form,
+    /// the form in lowercase.
+    LowerForm,
+    Lemma,
+    /// the lemma and the part-of-speech tag, as `lemma/POS`.
+    LemmaPos,
+    /// the part-of-speech tag alone.
+    Pos,
+}
+
+impl TokenUnit {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TokenUnit::Form => "form",
+            TokenUnit::LowerForm => "lower",
+            TokenUnit::Lemma => "lemma",
+            TokenUnit::LemmaPos => "lemma/pos",
+            TokenUnit::Pos => "pos",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TokenUnit> {
+        match name {
+            "form" => Some(TokenUnit::Form),
+            "lower" => Some(TokenUnit::LowerForm),
+            "lemma" => Some(TokenUnit::Lemma),
+            "lemma/pos" => Some(TokenUnit::LemmaPos),
+            "pos" => Some(TokenUnit::Pos),
+            _ => None,
+        }
+    }
+
+    /// # example
+    /// ```
+    /// use doc_class::extraction::TokenUnit;
+    /// assert_eq!(TokenUnit::LowerForm.of("Houses", "house", "NNS"), "houses");
+    /// assert_eq!(TokenUnit::LemmaPos.of("Houses", "house", "NNS"), "house/NNS");
+    /// ```
+    pub fn of(&self, form: &str, lemma: &str, pos: &str) -> String {
+        match *self {
+            TokenUnit::Form => form.to_owned(),
+            TokenUnit::LowerForm => form.to_lowercase(),
+            TokenUnit::Lemma => lemma.to_owned(),
+            TokenUnit::LemmaPos => format!("{}/{}", lemma, pos),
+            TokenUnit::Pos => pos.to_owned(),
+        }
+    }
+}
+
+/// what the ngrams in a block are made of.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum NgramKind {
+    /// sequences of tokens.
+    Word,
+    /// characters within each token, marked with `<` at the beginning and
+    /// `>` at the end.
+    CharInWord,
+    /// characters across tokens, in the text with the tokens separated and
+    /// surrounded by spaces.
+    CharAcross,
+    /// sequences of word clusters, skipping those with unclustered tokens.
+    Cluster,
+    /// lemma pairs of dependent and head.
+    HeadDep,
+    /// labelled arcs, i.e. lemma of dependent, relation, and lemma of head.
+    Arc,
+    /// lemmas along paths of two arcs, from a dependent over its head to the
+    /// head of its head.
+    DepPath,
+}
+
+impl NgramKind {
+    /// the prefix in spec names.
+    pub fn prefix(&self) -> &'static str {
+        match *self {
+            NgramKind::Word => "",
+            NgramKind::CharInWord => "c",
+            NgramKind::CharAcross => "x",
+            NgramKind::Cluster => "k",
+            NgramKind::HeadDep => "d",
+            NgramKind::Arc => "l",
+            NgramKind::DepPath => "p",
+        }
+    }
+
+    /// whether the ngrams come from dependency trees, which have a fixed size
+    /// and no `N` in their names.
+    pub fn is_syntactic(&self) -> bool {
+        matches!(*self, NgramKind::HeadDep | NgramKind::Arc | NgramKind::DepPath)
+    }
+}
+
+/// a token in a dependency tree, as read from the columns of conll-x.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct DepToken {
+    pub lemma: String,
+    /// the position of the head in the sentence, from `0`; `None` for the root.
+    pub head: Option<usize>,
+    pub rel: String,
+    /// dependency features are only extracted between tokens which are no
+    /// stopwords, but paths may pass through stopwords.
+    pub stopword: bool,
+}
+
+/// a token of a document before filtering, with the columns of conll-x which
+/// the ngrams are made of. a token without a form yields no ngrams, but keeps
+/// the positions of the others for the heads; see `document_terms` for
+/// tokens without a lemma or tag.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+pub struct Token {
+    pub form: Option<String>,
+    pub lemma: Option<String>,
+    pub pos: Option<String>,
+    /// the position of the head in the sentence, from `0`; `None` for the root.
+    pub head: Option<usize>,
+    pub rel: Option<String>,
+}
+
+impl Token {
+    /// an untagged token, as from a tokenizer.
+    pub fn untagged(form: &str) -> Self {
+        Token {
+            form: Some(form.to_owned()),
+            ..Token::default()
+        }
+    }
+
+    pub fn new(form: &str, lemma: &str, pos: &str) -> Self {
+        Token {
+            form: Some(form.to_owned()),
+            lemma: Some(lemma.to_owned()),
+            pos: Some(pos.to_owned()),
+            head: None,
+            rel: None,
+        }
+    }
+
+    /// attaches the token to the one at `head` with `rel`.
+    pub fn with_head(mut self, head: usize, rel: &str) -> Self {
+        self.head = Some(head);
+        self.rel = Some(rel.to_owned());
+        self
+    }
+}
+
+/// one block of hashed ngrams, with `min_n` to `max_n` tokens or characters.
+/// word ngrams are contiguous when `skip` is `0`, otherwise they are
+/// `skip`-skip-ngrams, i.e. tokens in order with at most `skip` tokens left
+/// out in between, which includes the contiguous ones.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct NgramSpec {
+    pub kind: NgramKind,
+    pub min_n: usize,
+    pub max_n: usize,
+    pub skip: usize,
+    pub dim: usize,
+}
+
+impl NgramSpec {
+    /// the default number of skipped tokens for `sN`.
+    pub const DEFAULT_SKIP: usize = 2;
+
+    /// `N` for contiguous ngrams, `sN` for skip-grams with the default skip,
+    /// or `KsN` with the skip `K`; `cN` for characters within words, and `xN`
+    /// for characters across words, `kN` for word clusters. `N` may also be a
+    /// range `N-M`. `d`, `l`,
+    /// and `p` are for head-dependent pairs, labelled arcs, and paths of two
+    /// arcs.
+    pub fn name(&self) -> String {
+        if self.kind.is_syntactic() {
+            return self.kind.prefix().to_owned();
+        }
+        let range = if self.min_n == self.max_n {
+            self.min_n.to_string()
+        } else {
+            format!("{}-{}", self.min_n, self.max_n)
+        };
+        match self.skip {
+            0 => format!("{}{}", self.kind.prefix(), range),
+            NgramSpec::DEFAULT_SKIP => format!("s{}", range),
+            skip => format!("{}s{}", skip, range),
+        }
+    }
+
+    /// parses `NAME:DIM`, where `DIM` is a product of numbers or powers like
+    /// `2^24` or `3*2^20`.
+    ///
+    /// # example
+    /// ```
+    /// use doc_class::extraction::{NgramSpec, NgramKind};
+    /// let spec = NgramSpec::parse("s2:3*2^10").unwrap();
+    /// assert_eq!(spec, NgramSpec { kind: NgramKind::Word, min_n: 2, max_n: 2, skip: 2, dim: 3072 });
+    /// assert_eq!(NgramSpec::parse("1s3:8").unwrap().skip, 1);
+    /// let spec = NgramSpec::parse("c3-5:2^22").unwrap();
+    /// assert_eq!((spec.kind, spec.min_n, spec.max_n), (NgramKind::CharInWord, 3, 5));
+    /// assert!(NgramSpec::parse("0:8").is_err());
+    /// assert!(NgramSpec::parse("3-2:8").is_err());
+    /// assert_eq!(NgramSpec::parse("l:2^20").unwrap().kind, NgramKind::Arc);
+    /// ```
+    pub fn parse(spec: &str) -> Result<NgramSpec, FormatError> {
+        let err = || FormatError::new(spec);
+        let x = spec.find(':').ok_or_else(err)?;
+        let (name, dim) = (&spec[..x], parse_dim(&spec[x + 1..]).ok_or_else(err)?);
+        let syntactic = [NgramKind::HeadDep, NgramKind::Arc, NgramKind::DepPath]
+            .iter()
+            .find(|kind| kind.prefix() == name)
+            .cloned();
+        if let Some(kind) = syntactic {
+            let n = if kind == NgramKind::DepPath { 3 } else { 2 };
+            return Ok(NgramSpec {
+                kind,
+                min_n: n,
+                max_n: n,
+                skip: 0,
+                dim,
+            });
+        }
+        let (kind, name) = if let Some(name) = name.strip_prefix('c') {
+            (NgramKind::CharInWord, name)
+        } else if let Some(name) = name.strip_prefix('x') {
+            (NgramKind::CharAcross, name)
+        } else if let Some(name) = name.strip_prefix('k') {
+            (NgramKind::Cluster, name)
+        } else {
+            (NgramKind::Word, name)
+        };
+        let (skip, range) = match name.find('s') {
+            _ if kind != NgramKind::Word => (0, name),
+            None => (0, name),
+            Some(0) => (NgramSpec::DEFAULT_SKIP, &name[1..]),
+            Some(x) => (name[..x].parse().map_err(|_| err())?, &name[x + 1..]),
+        };
+        let (min_n, max_n) = match range.find('-') {
+            None => {
+                let n = range.parse().map_err(|_| err())?;
+                (n, n)
+            }
+            Some(x) => (range[..x].parse().map_err(|_| err())?,
+                        range[x + 1..].parse().map_err(|_| err())?),
+        };
+        if min_n == 0 || max_n < min_n {
+            return Err(err());
+        }
+        Ok(NgramSpec {
+            kind,
+            min_n,
+            max_n,
+            skip,
+            dim,
+        })
+    }
+
+    /// calls `f` with the positions of each word ngram in a sequence of `len`
+    /// tokens.
+    fn for_each<F>(&self, len: usize, mut f: F)
+        where F: FnMut(&[usize])
+    {
+        let mut positions = Vec::with_capacity(self.max_n);
+        for n in self.min_n..self.max_n + 1 {
+            for start in 0..len {
+                positions.push(start);
+                extend(n, len, &mut positions, self.skip, &mut f);
+                positions.clear();
+            }
+        }
+    }
+}
+
+fn extend<F>(n: usize, len: usize, positions: &mut Vec<usize>, skip: usize, f: &mut F)
+    where F: FnMut(&[usize])
+{
+    if positions.len() == n {
+        return f(positions);
+    }
+    let next = positions[positions.len() - 1] + 1;
+    for s in 0..skip + 1 {
+        if len <= next + s {
+            break;
+        }
+        positions.push(next + s);
+        extend(n, len, positions, skip - s, f);
+        positions.pop();
+    }
+}
+
+/// calls `f` with each character ngram of `chars` with `min_n` to `max_n`
+/// characters.
+fn for_each_char_ngram<F>(chars: &[char], min_n: usize, max_n: usize, f: &mut F)
+    where F: FnMut(&str)
+{
+    let mut ngram = String::new();
+    for n in min_n..max_n + 1 {
+        for window in chars.windows(n) {
+            ngram.clear();
+            ngram.extend(window);
+            f(&ngram)
+        }
+    }
+}
+
+/// parses a comma-separated list of `NgramSpec`s.
+pub fn parse_specs(specs: &str) -> Result<Vec<NgramSpec>, FormatError> {
+    specs.split(',').map(|spec| NgramSpec::parse(spec.trim())).collect()
+}
+
+/// the inverse of `parse_specs`, with plain numbers for the dimensions.
+pub fn format_specs(specs: &[NgramSpec]) -> String {
+    specs.iter().map(|spec| format!("{}:{}", spec.name(), spec.dim)).collect::<Vec<_>>().join(",")
+}
+
+fn parse_dim(dim: &str) -> Option<usize> {
+    let mut product = 1usize;
+    for factor in dim.split('*') {
+        let mut parts = factor.splitn(2, '^');
+        let base = parts.next()?.trim().parse::<usize>().ok()?;
+        let value = match parts.next() {
+            Some(exp) => base.checked_pow(exp.trim().parse().ok()?)?,
+            None => base,
+        };
+        product = product.checked_mul(value)?;
+    }
+    Some(product)
+}
+
+/// hashes the ngrams of a token sequence into consecutive blocks of
+/// dimensions, one block for each spec.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct NgramExtractor {
+    unit: TokenUnit,
+    specs: Vec<NgramSpec>,
+    hasher: FeatureHasher,
+    clusters: Option<(String, Arc<HashMap<String, usize>>)>,
+}
+
+impl NgramExtractor {
+    /// a spec with `0` dimensions is skipped.
+    pub fn new(unit: TokenUnit, specs: Vec<NgramSpec>, hasher: FeatureHasher) -> Self {
+        NgramExtractor {
+            unit,
+            specs,
+            hasher,
+            clusters: None,
+        }
+    }
+
+    /// sets the word clusters for the `Cluster` blocks, read from `path`,
+    /// which is kept for saving.
+    pub fn with_clusters(mut self, path: &str, clusters: Arc<HashMap<String, usize>>) -> Self {
+        self.clusters = Some((path.to_owned(), clusters));
+        self
+    }
+
+    /// the path of the word clusters, if any.
+    pub fn clusters_path(&self) -> Option<&str> {
+        self.clusters.as_ref().map(|(path, _)| path.as_str())
+    }
+
+    pub fn unit(&self) -> TokenUnit {
+        self.unit
+    }
+
+    pub fn specs(&self) -> &[NgramSpec] {
+        &self.specs
+    }
+
+    pub fn hasher(&self) -> FeatureHasher {
+        self.hasher
+    }
+
+    /// the total number of dimensions over all blocks.
+    pub fn dim(&self) -> usize {
+        self.specs.iter().map(|spec| spec.dim).sum()
+    }
+
+    /// returns the hashed ngrams of `tokens`, already turned into units, with
+    /// their signs.
+    ///
+    /// # example
+    /// ```
+    /// use doc_class::extraction::{NgramExtractor, TokenUnit, parse_specs};
+    /// use doc_class::hashing::FeatureHasher;
+    /// let specs = parse_specs("1:2^4,1s2:2^4,c2-3:2^4").unwrap();
+    /// let extractor = NgramExtractor::new(TokenUnit::Form, specs, FeatureHasher::default());
+    /// let terms = extractor.extract(&["a", "b", "c"]);
+    /// // 3 unigrams, the skip-bigrams `a b`, `a c`, and `b c`, then `<a`, `a>`,
+    /// // and `<a>` for each of the 3 tokens.
+    /// assert_eq!(terms.len(), 15);
+    /// assert!(terms[..3].iter().all(|&(i, _)| i < 16));
+    /// assert!(terms[3..6].iter().all(|&(i, _)| 16 <= i && i < 32));
+    /// assert!(terms[6..].iter().all(|&(i, _)| 32 <= i && i < 48));
+    /// ```
+    pub fn extract<S>(&self, tokens: &[S]) -> Vec<(usize, isize)>
+        where S: AsRef<str>
+    {
+        let mut terms = Vec::new();
+        self.for_each_term(tokens, |term, sign, _, _| terms.push((term, sign)));
+        terms
+    }
+
+    /// returns what `extract` does, with the name of each ngram, which is
+    /// the spec name and the parts of the ngram separated by spaces, like
+    /// `2:new york` or `c3:<ne`.
+    pub fn extract_named<S>(&self, tokens: &[S]) -> Vec<(usize, isize, String)>
+        where S: AsRef<str>
+    {
+        let mut terms = Vec::new();
+        self.for_each_term(tokens, |term, sign, spec, ngram| {
+            terms.push((term, sign, ngram_name(spec, ngram)))
+        });
+        terms
+    }
+
+    /// calls `f` with the term, the sign, the spec, and the parts of each
+    /// ngram from the non-syntactic blocks.
+    fn for_each_term<S, F>(&self, tokens: &[S], mut f: F)
+        where S: AsRef<str>,
+              F: FnMut(usize, isize, &NgramSpec, &[&str])
+    {
+        let mut offset = 0;
+        let mut ngram = Vec::new();
+        for spec in &self.specs {
+            if spec.dim == 0 {
+                continue;
+            }
+            let mut push = |ngram: &[&str]| {
+                let (i, sign) = self.hasher.index(ngram, spec.dim);
+                f(offset + i, sign, spec, ngram)
+            };
+            match spec.kind {
+                NgramKind::Word => {
+                    spec.for_each(tokens.len(), |positions| {
+                        ngram.clear();
+                        ngram.extend(positions.iter().map(|&i| tokens[i].as_ref()));
+                        push(&ngram)
+                    })
+                }
+                NgramKind::CharInWord => {
+                    for token in tokens {
+                        let mut chars = vec!['<'];
+                        chars.extend(token.as_ref().chars());
+                        chars.push('>');
+                        for_each_char_ngram(&chars, spec.min_n, spec.max_n,
+                                            &mut |ngram| push(&[ngram]))
+                    }
+                }
+                NgramKind::CharAcross => {
+                    let mut chars = vec![' '];
+                    for token in tokens {
+                        chars.extend(token.as_ref().chars());
+                        chars.push(' ');
+                    }
+                    for_each_char_ngram(&chars, spec.min_n, spec.max_n,
+                                        &mut |ngram| push(&[ngram]))
+                }
+                NgramKind::Cluster => {
+                    let clusters = match self.clusters {
+                        Some((_, ref clusters)) => clusters,
+                        None => panic!("no word clusters for {}", spec.name()),
+                    };
+                    let ids = tokens.iter()
+                        .map(|token| clusters.get(token.as_ref()).map(ToString::to_string))
+                        .collect::<Vec<_>>();
+                    for n in spec.min_n..spec.max_n + 1 {
+                        for window in ids.windows(n) {
+                            let ngram = window.iter().map(Option::as_deref).collect::<Option<Vec<_>>>();
+                            if let Some(ngram) = ngram {
+                                push(&ngram)
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            offset += spec.dim;
+        }
+    }
+
+    /// returns the hashed features from the blocks of dependency kinds, with
+    /// their signs; the other blocks are left for `extract`.
+    ///
+    /// # example
+    /// ```
+    /// use doc_class::extraction::{NgramExtractor, TokenUnit, DepToken, parse_specs};
+    /// use doc_class::hashing::FeatureHasher;
+    /// let token = |lemma: &str, head, rel: &str| {
+    ///     DepToken { lemma: lemma.to_owned(), head, rel: rel.to_owned(), stopword: false }
+    /// };
+    /// // `hunt` is the root, with `dog` as its subject and `cat` as its object.
+    /// let sentence = vec![token("dog", Some(1), "nsubj"), token("hunt", None, "root"),
+    ///                     token("cat", Some(1), "dobj")];
+    /// let specs = parse_specs("1:2^4,d:2^4,l:2^4,p:2^4").unwrap();
+    /// let extractor = NgramExtractor::new(TokenUnit::Lemma, specs, FeatureHasher::default());
+    /// let terms = extractor.extract_deps(&[sentence]);
+    /// // 2 pairs, 2 arcs, and no paths.
+    /// assert_eq!(terms.len(), 4);
+    /// assert!(terms.iter().all(|&(i, _)| 16 <= i && i < 48));
+    /// ```
+    pub fn extract_deps<S>(&self, sentences: &[S]) -> Vec<(usize, isize)>
+        where S: AsRef<[DepToken]>
+    {
+        let mut terms = Vec::new();
+        self.for_each_dep_term(sentences, |term, sign, _, _| terms.push((term, sign)));
+        terms
+    }
+
+    /// returns what `extract_deps` does, with the names as for
+    /// `extract_named`, like `l:dog nsubj hunt`.
+    pub fn extract_deps_named<S>(&self, sentences: &[S]) -> Vec<(usize, isize, String)>
+        where S: AsRef<[DepToken]>
+    {
+        let mut terms = Vec::new();
+        self.for_each_dep_term(sentences, |term, sign, spec, ngram| {
+            terms.push((term, sign, ngram_name(spec, ngram)))
+        });
+        terms
+    }
+
+    fn for_each_dep_term<S, F>(&self, sentences: &[S], mut f: F)
+        where S: AsRef<[DepToken]>,
+              F: FnMut(usize, isize, &NgramSpec, &[&str])
+    {
+        let mut offset = 0;
+        for spec in &self.specs {
+            if spec.dim == 0 || !spec.kind.is_syntactic() {
+                offset += spec.dim;
+                continue;
+            }
+            for sentence in sentences {
+                let sentence = sentence.as_ref();
+                for dep in sentence {
+                    let head = match dep.head.and_then(|h| sentence.get(h)) {
+                        Some(head) => head,
+                        None => continue,
+                    };
+                    let ngram = match spec.kind {
+                        NgramKind::HeadDep if !dep.stopword && !head.stopword => {
+                            vec![dep.lemma.as_str(), &head.lemma]
+                        }
+                        NgramKind::Arc if !dep.stopword && !head.stopword => {
+                            vec![dep.lemma.as_str(), &dep.rel, &head.lemma]
+                        }
+                        NgramKind::DepPath if !dep.stopword => {
+                            match head.head.and_then(|h| sentence.get(h)) {
+                                Some(grand) if !grand.stopword => {
+                                    vec![dep.lemma.as_str(), &head.lemma, &grand.lemma]
+                                }
+                                _ => continue,
+                            }
+                        }
+                        _ => continue,
+                    };
+                    let (i, sign) = self.hasher.index(&ngram, spec.dim);
+                    f(offset + i, sign, spec, &ngram)
+                }
+            }
+            offset += spec.dim;
+        }
+    }
+}
+
+fn ngram_name(spec: &NgramSpec, ngram: &[&str]) -> String {
+    format!("{}:{}", spec.name(), ngram.join(" "))
+}
+
+/// reads word clusters as written by `kmeans`, one `word\tcluster` per line.
+pub fn read_clusters<R>(rdr: R) -> Result<HashMap<String, usize>, LoadError>
+    where R: BufRead
+{
+    let mut clusters = HashMap::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        if line.is_empty() {
+            continue;
+        }
+        let x = line.rfind('\t').ok_or_else(|| FormatError::new(&line))?;
+        clusters.insert(line[..x].to_owned(), line[x + 1..].trim().parse()?);
+    }
+    Ok(clusters)
+}
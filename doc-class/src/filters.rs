@@ -1,6 +1,9 @@
 // Author: Daniël de Kok
 // https://github.com/danieldk/ir-examples/blob/master/classify/src/filters.rs
 
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+
 /// A `StopwordFilter` filters words based on their lemma and/or tag.
 pub trait StopwordFilter {
     fn is_stopword(&self, tag: &str, lemma: &str) -> bool;
@@ -27,3 +30,230 @@ fn ptb_open_class(tag: &str) -> bool {
     tag == "CD" || tag == "FW" || tag.starts_with("JJ") || tag.starts_with("NN") ||
         tag.starts_with("NP") || tag.starts_with("RB") || tag.starts_with("VB")
 }
+
+/// A filter for stopwords with STTS tags, as in TüBa-D/Z and TüBa-D/W, which
+/// keeps the open classes except for the auxiliaries. Lemmas like
+/// `werden%passiv` are compared without their `%` suffix.
+pub struct STTSStopwordFilter;
+
+impl StopwordFilter for STTSStopwordFilter {
+    fn is_stopword(&self, tag: &str, lemma: &str) -> bool {
+        if !stts_open_class(tag) {
+            return true;
+        }
+
+        let lemma = lemma.split('%').next().unwrap_or(lemma);
+        if lemma == "sein" || lemma == "haben" || lemma == "werden" {
+            return true;
+        }
+
+        false
+    }
+}
+
+fn stts_open_class(tag: &str) -> bool {
+    tag == "CARD" || tag == "FM" || tag == "NN" || tag == "NE" || tag == "ADV" ||
+        tag.starts_with("ADJ") || tag.starts_with("VV") || tag.starts_with("VA")
+}
+
+/// A filter for stopwords from lists of tags and lemmas. A tag ending in `*`
+/// matches all tags with the part before as prefix.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+pub struct ListStopwordFilter {
+    tags: HashSet<String>,
+    tag_prefixes: Vec<String>,
+    lemmas: HashSet<String>,
+}
+
+impl ListStopwordFilter {
+    pub fn new<I, J>(tags: I, lemmas: J) -> Self
+        where I: IntoIterator<Item = String>,
+              J: IntoIterator<Item = String>
+    {
+        let mut filter = ListStopwordFilter::default();
+        for tag in tags {
+            if tag.ends_with('*') {
+                filter.tag_prefixes.push(tag[..tag.len() - 1].to_owned());
+            } else {
+                filter.tags.insert(tag);
+            }
+        }
+        filter.tag_prefixes.sort();
+        filter.lemmas.extend(lemmas);
+        filter
+    }
+
+    /// Reads the lists with one entry per line, skipping empty lines and
+    /// lines starting with `#`.
+    pub fn read<R, S>(tags: Option<R>, lemmas: Option<S>) -> io::Result<Self>
+        where R: BufRead,
+              S: BufRead
+    {
+        let tags = match tags {
+            Some(rdr) => read_list(rdr)?,
+            None => Vec::new(),
+        };
+        let lemmas = match lemmas {
+            Some(rdr) => read_list(rdr)?,
+            None => Vec::new(),
+        };
+        Ok(ListStopwordFilter::new(tags, lemmas))
+    }
+
+    /// The tags, with the prefixes marked by `*`, sorted.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = self.tags.iter().cloned().collect::<Vec<_>>();
+        tags.extend(self.tag_prefixes.iter().map(|prefix| format!("{}*", prefix)));
+        tags.sort();
+        tags
+    }
+
+    /// The lemmas, sorted.
+    pub fn lemmas(&self) -> Vec<String> {
+        let mut lemmas = self.lemmas.iter().cloned().collect::<Vec<_>>();
+        lemmas.sort();
+        lemmas
+    }
+}
+
+impl StopwordFilter for ListStopwordFilter {
+    fn is_stopword(&self, tag: &str, lemma: &str) -> bool {
+        self.tags.contains(tag) || self.lemmas.contains(lemma) ||
+            self.tag_prefixes.iter().any(|prefix| tag.starts_with(prefix.as_str()))
+    }
+}
+
+fn read_list<R>(rdr: R) -> io::Result<Vec<String>>
+    where R: BufRead
+{
+    let mut list = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        let entry = line.trim();
+        if !entry.is_empty() && !entry.starts_with('#') {
+            list.push(entry.to_owned());
+        }
+    }
+    Ok(list)
+}
+
+/// One of the filters above, or none, chosen at run time.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Stopwords {
+    NoFilter,
+    Ptb,
+    Stts,
+    Lists(ListStopwordFilter),
+}
+
+impl Stopwords {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Stopwords::NoFilter => "none",
+            Stopwords::Ptb => "ptb",
+            Stopwords::Stts => "stts",
+            Stopwords::Lists(_) => "lists",
+        }
+    }
+
+    /// Returns the filters without lists by their names.
+    pub fn from_name(name: &str) -> Option<Stopwords> {
+        match name {
+            "none" => Some(Stopwords::NoFilter),
+            "ptb" => Some(Stopwords::Ptb),
+            "stts" => Some(Stopwords::Stts),
+            _ => None,
+        }
+    }
+
+    /// Whether a token without a tag is a stopword. Only the lemmas of
+    /// `Lists` apply, since the other filters need tags.
+    pub fn is_untagged_stopword(&self, lemma: &str) -> bool {
+        match *self {
+            Stopwords::Lists(ref filter) => filter.lemmas.contains(lemma),
+            _ => false,
+        }
+    }
+}
+
+impl StopwordFilter for Stopwords {
+    fn is_stopword(&self, tag: &str, lemma: &str) -> bool {
+        match *self {
+            Stopwords::NoFilter => false,
+            Stopwords::Ptb => PTBStopwordFilter.is_stopword(tag, lemma),
+            Stopwords::Stts => STTSStopwordFilter.is_stopword(tag, lemma),
+            Stopwords::Lists(ref filter) => filter.is_stopword(tag, lemma),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stts_filter_drops_auxiliaries() {
+        let filter = STTSStopwordFilter;
+        for &(tag, lemma) in &[("VAFIN", "sein"),
+                               ("VAINF", "haben"),
+                               ("VAFIN", "werden"),
+                               ("VAFIN", "werden%passiv"),
+                               ("VAPP", "sein%aux")] {
+            assert!(filter.is_stopword(tag, lemma), "{} {}", tag, lemma);
+        }
+        assert!(!filter.is_stopword("VVFIN", "gehen"));
+        assert!(!filter.is_stopword("VAFIN", "müssen"));
+        // Only the part before the `%` is compared.
+        assert!(!filter.is_stopword("VVFIN", "werdend%sein"));
+    }
+
+    #[test]
+    fn stts_filter_keeps_the_open_classes() {
+        let filter = STTSStopwordFilter;
+        for &tag in &["CARD", "FM", "NN", "NE", "ADV", "ADJA", "ADJD", "VVFIN", "VVPP", "VAFIN"] {
+            assert!(!filter.is_stopword(tag, "x"), "{}", tag);
+        }
+        for &tag in &["ART", "APPR", "KON", "PPER", "PDS", "PTKNEG", "VMFIN", "$.", "NNE", ""] {
+            assert!(filter.is_stopword(tag, "x"), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn list_filter_reads_prefixes_and_skips_comments() {
+        let tags = "# function words\nART\n\n  V*  \nPP*\n";
+        let lemmas = "der\n# not a lemma\ndie\n";
+        let filter = ListStopwordFilter::read(Some(tags.as_bytes()), Some(lemmas.as_bytes()))
+            .unwrap();
+        assert_eq!(filter.tags(), ["ART", "PP*", "V*"]);
+        assert_eq!(filter.lemmas(), ["der", "die"]);
+
+        assert!(filter.is_stopword("ART", "ein"));
+        assert!(!filter.is_stopword("ARTX", "ein"));
+        assert!(filter.is_stopword("VVFIN", "gehen"));
+        assert!(filter.is_stopword("V", "gehen"));
+        assert!(filter.is_stopword("PPER", "er"));
+        assert!(!filter.is_stopword("PDS", "er"));
+        assert!(filter.is_stopword("NN", "der"));
+        assert!(!filter.is_stopword("NN", "#"));
+        assert!(!filter.is_stopword("NN", "Haus"));
+    }
+
+    #[test]
+    fn list_filter_with_only_tags_or_only_lemmas() {
+        let filter = ListStopwordFilter::read(Some("NN\n".as_bytes()), None::<&[u8]>).unwrap();
+        assert!(filter.lemmas().is_empty());
+        assert!(filter.is_stopword("NN", "Haus"));
+        assert!(!filter.is_stopword("NE", "NN"));
+        assert!(!Stopwords::Lists(filter).is_untagged_stopword("NN"));
+
+        let filter = ListStopwordFilter::read(None::<&[u8]>, Some("Haus\n".as_bytes())).unwrap();
+        assert!(filter.tags().is_empty());
+        assert!(filter.is_stopword("NE", "Haus"));
+        assert!(!filter.is_stopword("Haus", "Hof"));
+        assert!(Stopwords::Lists(filter).is_untagged_stopword("Haus"));
+
+        let filter = ListStopwordFilter::read(None::<&[u8]>, None::<&[u8]>).unwrap();
+        assert_eq!(filter, ListStopwordFilter::default());
+        assert!(!filter.is_stopword("", ""));
+    }
+}
@@ -0,0 +1,606 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::SparseVec;
+use error::{LoadError, FormatError};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+/// file formats for a labelled document feature matrix. the text formats
+/// number the dimensions from `1`, the binary ones from `0`.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Format {
+    /// svmlight / liblinear: `label dim:val ...`.
+    Svm,
+    /// vowpal wabbit: `label | dim:val ...`, with labels from `1` for `--oaa`.
+    Vw,
+    /// matrix market coordinate format, with the labels in `PATH.labels`.
+    Mtx,
+    /// csr arrays for numpy in `PATH.data.npy`, `PATH.indices.npy`, and
+    /// `PATH.indptr.npy`, with the labels in `PATH.labels.npy`.
+    Npy,
+    /// a compact little-endian csr format; see `write_bin`.
+    Bin,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Svm => "svm",
+            Format::Vw => "vw",
+            Format::Mtx => "mtx",
+            Format::Npy => "npy",
+            Format::Bin => "bin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "svm" => Some(Format::Svm),
+            "vw" => Some(Format::Vw),
+            "mtx" => Some(Format::Mtx),
+            "npy" => Some(Format::Npy),
+            "bin" => Some(Format::Bin),
+            _ => None,
+        }
+    }
+}
+
+/// writes `labels` and `features` with `dim` columns to `path` in `format`,
+/// plus the companion files for `Mtx` and `Npy`.
+pub fn write_features(format: Format,
+                      path: &str,
+                      labels: &[usize],
+                      features: &[SparseVec<f32>],
+                      dim: usize)
+                      -> io::Result<()> {
+    match format {
+        Format::Svm => write_svm(&mut BufWriter::new(File::create(path)?), labels, features),
+        Format::Vw => write_vw(&mut BufWriter::new(File::create(path)?), labels, features),
+        Format::Mtx => {
+            write_mtx(&mut BufWriter::new(File::create(path)?), features, dim)?;
+            let mut wtr = BufWriter::new(File::create(format!("{}.labels", path))?);
+            for label in labels {
+                writeln!(wtr, "{}", label)?;
+            }
+            Ok(())
+        }
+        Format::Npy => {
+            let (data, indices, indptr) = to_csr(features);
+            write_npy(&mut BufWriter::new(File::create(format!("{}.data.npy", path))?), &data)?;
+            // `int32` would be enough for scipy up to `2^31` columns, but the
+            // hashed dimensions may go beyond.
+            let indices = indices.into_iter().map(|i| i as i64).collect::<Vec<_>>();
+            write_npy(&mut BufWriter::new(File::create(format!("{}.indices.npy", path))?),
+                      &indices)?;
+            let indptr = indptr.into_iter().map(|i| i as i64).collect::<Vec<_>>();
+            write_npy(&mut BufWriter::new(File::create(format!("{}.indptr.npy", path))?),
+                      &indptr)?;
+            let labels = labels.iter().map(|&l| l as i64).collect::<Vec<_>>();
+            write_npy(&mut BufWriter::new(File::create(format!("{}.labels.npy", path))?),
+                      &labels)
+        }
+        Format::Bin => write_bin(&mut BufWriter::new(File::create(path)?), labels, features, dim),
+    }
+}
+
+/// reads what `write_features` wrote.
+pub fn read_features(format: Format, path: &str)
+                     -> Result<(Vec<usize>, Vec<SparseVec<f32>>), LoadError> {
+    match format {
+        Format::Svm => read_svm(BufReader::new(File::open(path)?)),
+        Format::Vw => read_vw(BufReader::new(File::open(path)?)),
+        Format::Mtx => {
+            let features = read_mtx(BufReader::new(File::open(path)?))?;
+            let mut labels = Vec::new();
+            for res_line in BufReader::new(File::open(format!("{}.labels", path))?).lines() {
+                labels.push(res_line?.trim().parse()?);
+            }
+            Ok((labels, features))
+        }
+        Format::Npy => {
+            let data = read_npy::<_, f32>(BufReader::new(File::open(format!("{}.data.npy", path))?))?;
+            let indices = read_npy::<_, i64>(BufReader::new(File::open(format!("{}.indices.npy", path))?))?;
+            let indptr = read_npy::<_, i64>(BufReader::new(File::open(format!("{}.indptr.npy", path))?))?;
+            let labels = read_npy::<_, i64>(BufReader::new(File::open(format!("{}.labels.npy", path))?))?;
+            let features = from_csr(&data, &to_usizes(indices)?, &to_usizes(indptr)?)?;
+            Ok((to_usizes(labels)?, features))
+        }
+        Format::Bin => read_bin(BufReader::new(File::open(path)?)),
+    }
+}
+
+/// returns `(data, indices, indptr)`, the compressed sparse rows of `features`.
+pub fn to_csr(features: &[SparseVec<f32>]) -> (Vec<f32>, Vec<usize>, Vec<usize>) {
+    let mut data = Vec::new();
+    let mut indices = Vec::new();
+    let mut indptr = vec![0];
+    for feat_vec in features {
+        for &(dim, feat) in feat_vec {
+            indices.push(dim);
+            data.push(feat);
+        }
+        indptr.push(data.len());
+    }
+    (data, indices, indptr)
+}
+
+/// returns the rows of the compressed sparse rows `(data, indices, indptr)`,
+/// checking that `indptr` starts at `0`, does not decrease, and ends at the
+/// number of values, as `CsrMatrix::from_parts` does.
+pub fn from_csr(data: &[f32], indices: &[usize], indptr: &[usize])
+                -> Result<Vec<SparseVec<f32>>, FormatError> {
+    if data.len() != indices.len() || indptr.first() != Some(&0) ||
+       indptr.last() != Some(&data.len()) || indptr.windows(2).any(|w| w[1] < w[0]) {
+        return Err(FormatError::new("inconsistent csr arrays"));
+    }
+    Ok(indptr.windows(2)
+        .map(|w| indices[w[0]..w[1]].iter().cloned().zip(data[w[0]..w[1]].iter().cloned()).collect())
+        .collect())
+}
+
+pub fn write_svm<W>(wtr: &mut W, labels: &[usize], features: &[SparseVec<f32>]) -> io::Result<()>
+    where W: Write
+{
+    for (label, dim2feat) in labels.iter().zip(features.iter()) {
+        write!(wtr, "{}", label)?;
+        for &(dim, feat) in dim2feat {
+            write!(wtr, " {}:{}", dim + 1, feat)?;
+        }
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+pub fn read_svm<R>(rdr: R) -> Result<(Vec<usize>, Vec<SparseVec<f32>>), LoadError>
+    where R: BufRead
+{
+    let mut labels = Vec::new();
+    let mut features = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        let mut fields = line.split_whitespace();
+        labels.push(fields.next().ok_or_else(|| FormatError::new(&line))?.parse()?);
+        features.push(parse_dim_feats(fields, &line)?);
+    }
+    Ok((labels, features))
+}
+
+/// the labels of a row with several.
+pub type LabelSet = Vec<usize>;
+
+/// writes the svmlight format for several labels per row, as in the
+/// multi-label data sets of libsvm, with the labels separated by commas; a
+/// row without labels starts with a space.
+pub fn write_multi_svm<W>(wtr: &mut W, label_sets: &[LabelSet], features: &[SparseVec<f32>])
+                          -> io::Result<()>
+    where W: Write
+{
+    for (labels, dim2feat) in label_sets.iter().zip(features.iter()) {
+        let labels = labels.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(wtr, "{}", labels.join(","))?;
+        for &(dim, feat) in dim2feat {
+            write!(wtr, " {}:{}", dim + 1, feat)?;
+        }
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+pub fn read_multi_svm<R>(rdr: R) -> Result<(Vec<LabelSet>, Vec<SparseVec<f32>>), LoadError>
+    where R: BufRead
+{
+    let mut label_sets = Vec::new();
+    let mut features = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        let mut fields = line.split(' ');
+        let labels = fields.next().unwrap_or("");
+        label_sets.push(if labels.is_empty() {
+            Vec::new()
+        } else {
+            labels.split(',').map(str::parse).collect::<Result<_, _>>()?
+        });
+        features.push(parse_dim_feats(fields.filter(|field| !field.is_empty()), &line)?);
+    }
+    Ok((label_sets, features))
+}
+
+pub fn write_vw<W>(wtr: &mut W, labels: &[usize], features: &[SparseVec<f32>]) -> io::Result<()>
+    where W: Write
+{
+    for (label, dim2feat) in labels.iter().zip(features.iter()) {
+        write!(wtr, "{} |", label + 1)?;
+        for &(dim, feat) in dim2feat {
+            write!(wtr, " {}:{}", dim + 1, feat)?;
+        }
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+pub fn read_vw<R>(rdr: R) -> Result<(Vec<usize>, Vec<SparseVec<f32>>), LoadError>
+    where R: BufRead
+{
+    let mut labels = Vec::new();
+    let mut features = Vec::new();
+    for res_line in rdr.lines() {
+        let line = res_line?;
+        let x = line.find('|').ok_or_else(|| FormatError::new(&line))?;
+        let label = line[..x].trim().parse::<usize>()?;
+        if label == 0 {
+            return Err(FormatError::new(&line).into());
+        }
+        labels.push(label - 1);
+        features.push(parse_dim_feats(line[x + 1..].split_whitespace(), &line)?);
+    }
+    Ok((labels, features))
+}
+
+/// parses `dim:val` pairs with dimensions from `1`.
+fn parse_dim_feats<'a, I>(fields: I, line: &str) -> Result<SparseVec<f32>, LoadError>
+    where I: Iterator<Item = &'a str>
+{
+    let mut dim2feat = SparseVec::new();
+    for field in fields {
+        let x = field.find(':').ok_or_else(|| FormatError::new(line))?;
+        let dim = field[..x].parse::<usize>()?;
+        if dim == 0 {
+            return Err(FormatError::new(line).into());
+        }
+        dim2feat.insert(dim - 1, field[x + 1..].parse()?);
+    }
+    Ok(dim2feat)
+}
+
+pub fn write_mtx<W>(wtr: &mut W, features: &[SparseVec<f32>], dim: usize) -> io::Result<()>
+    where W: Write
+{
+    let nnz = features.iter().map(SparseVec::len).sum::<usize>();
+    writeln!(wtr, "%%MatrixMarket matrix coordinate real general")?;
+    writeln!(wtr, "{} {} {}", features.len(), dim, nnz)?;
+    for (row, dim2feat) in features.iter().enumerate() {
+        for &(dim, feat) in dim2feat {
+            writeln!(wtr, "{} {} {}", row + 1, dim + 1, feat)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_mtx<R>(rdr: R) -> Result<Vec<SparseVec<f32>>, LoadError>
+    where R: BufRead
+{
+    let mut lines = rdr.lines();
+    let mut features = None;
+    for res_line in &mut lines {
+        let line = res_line?;
+        if line.starts_with('%') || line.trim().is_empty() {
+            continue;
+        }
+        let rows = line.split_whitespace().next().ok_or_else(|| FormatError::new(&line))?;
+        features = Some(vec![SparseVec::new(); rows.parse()?]);
+        break;
+    }
+    let mut features = features.ok_or_else(|| FormatError::new("missing size line"))?;
+    for res_line in lines {
+        let line = res_line?;
+        let mut fields = line.split_whitespace();
+        let (row, dim, feat) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(row), Some(dim), Some(feat)) => (row.parse::<usize>()?, dim.parse::<usize>()?, feat.parse()?),
+            _ => return Err(FormatError::new(&line).into()),
+        };
+        if row == 0 || features.len() < row || dim == 0 {
+            return Err(FormatError::new(&line).into());
+        }
+        features[row - 1].insert(dim - 1, feat);
+    }
+    Ok(features)
+}
+
+/// element types for `.npy` arrays.
+pub trait NpyElem: Sized + Copy {
+    /// the numpy type descriptor.
+    const DESCR: &'static str;
+    const SIZE: usize;
+    fn write_le<W: Write>(&self, wtr: &mut W) -> io::Result<()>;
+    fn from_le(bytes: &[u8]) -> Self;
+}
+
+impl NpyElem for f32 {
+    const DESCR: &'static str = "<f4";
+    const SIZE: usize = 4;
+    fn write_le<W: Write>(&self, wtr: &mut W) -> io::Result<()> {
+        wtr.write_all(&self.to_le_bytes())
+    }
+    fn from_le(bytes: &[u8]) -> Self {
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+}
+
+impl NpyElem for i32 {
+    const DESCR: &'static str = "<i4";
+    const SIZE: usize = 4;
+    fn write_le<W: Write>(&self, wtr: &mut W) -> io::Result<()> {
+        wtr.write_all(&self.to_le_bytes())
+    }
+    fn from_le(bytes: &[u8]) -> Self {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+}
+
+impl NpyElem for i64 {
+    const DESCR: &'static str = "<i8";
+    const SIZE: usize = 8;
+    fn write_le<W: Write>(&self, wtr: &mut W) -> io::Result<()> {
+        wtr.write_all(&self.to_le_bytes())
+    }
+    fn from_le(bytes: &[u8]) -> Self {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[..8]);
+        i64::from_le_bytes(buf)
+    }
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// writes a one-dimensional `.npy` array, version 1.0.
+pub fn write_npy<W, T>(wtr: &mut W, array: &[T]) -> io::Result<()>
+    where W: Write, T: NpyElem
+{
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': ({},), }}",
+                             T::DESCR, array.len());
+    // the header is padded so that the data start at a multiple of 64 bytes.
+    while !(NPY_MAGIC.len() + 4 + header.len() + 1).is_multiple_of(64) {
+        header.push(' ')
+    }
+    header.push('\n');
+    wtr.write_all(NPY_MAGIC)?;
+    wtr.write_all(&[1, 0])?;
+    wtr.write_all(&(header.len() as u16).to_le_bytes())?;
+    wtr.write_all(header.as_bytes())?;
+    for x in array {
+        x.write_le(wtr)?;
+    }
+    Ok(())
+}
+
+/// reads a one-dimensional `.npy` array of `T`, version 1.0 or 2.0.
+pub fn read_npy<R, T>(mut rdr: R) -> Result<Vec<T>, LoadError>
+    where R: Read, T: NpyElem
+{
+    let mut magic = [0; 8];
+    rdr.read_exact(&mut magic)?;
+    if &magic[..6] != NPY_MAGIC {
+        return Err(FormatError::new("not a npy file").into());
+    }
+    let header_len = if magic[6] == 1 {
+        let mut buf = [0; 2];
+        rdr.read_exact(&mut buf)?;
+        u16::from_le_bytes(buf) as usize
+    } else {
+        let mut buf = [0; 4];
+        rdr.read_exact(&mut buf)?;
+        u32::from_le_bytes(buf) as usize
+    };
+    let header = read_bytes(&mut rdr, header_len, 1)?;
+    let header = String::from_utf8_lossy(&header);
+    if !header.contains(&format!("'descr': '{}'", T::DESCR)) || header.contains("'fortran_order': True") {
+        return Err(FormatError::new(&header).into());
+    }
+    let len = header.find("'shape': (")
+        .map(|x| &header[x + 10..])
+        .and_then(|rest| rest.split([',', ')']).next())
+        .ok_or_else(|| FormatError::new(&header))?
+        .trim()
+        .parse::<usize>()?;
+    let bytes = read_bytes(&mut rdr, len, T::SIZE)?;
+    Ok(bytes.chunks(T::SIZE).map(T::from_le).collect())
+}
+
+/// reads `n` items of `size` bytes. the buffer only grows with the bytes
+/// actually read, so that a corrupt size fails for a short file instead of
+/// allocating it all at once.
+fn read_bytes<R>(rdr: &mut R, n: usize, size: usize) -> Result<Vec<u8>, LoadError>
+    where R: Read
+{
+    let len = n.checked_mul(size).ok_or_else(|| FormatError::new("array size overflows"))?;
+    let mut bytes = Vec::new();
+    rdr.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(FormatError::new("array shorter than its size").into());
+    }
+    Ok(bytes)
+}
+
+/// converts indices read as `i64`, failing on negative ones.
+fn to_usizes(xs: Vec<i64>) -> Result<Vec<usize>, FormatError> {
+    xs.into_iter()
+        .map(|x| if x < 0 { Err(FormatError::new("negative index")) } else { Ok(x as usize) })
+        .collect()
+}
+
+const BIN_MAGIC: &[u8] = b"DCSR\x00\x00\x00\x01";
+
+/// writes the magic bytes `DCSR\0\0\0\x01`, then the numbers of rows, columns,
+/// and nonzeros as `u64`, followed by the labels and the row pointers as
+/// `u64`, the column indices as `u32`, and the values as `f32`. fails for
+/// columns beyond `u32`.
+pub fn write_bin<W>(wtr: &mut W, labels: &[usize], features: &[SparseVec<f32>], dim: usize)
+                    -> io::Result<()>
+    where W: Write
+{
+    let (data, indices, indptr) = to_csr(features);
+    if indices.iter().any(|&i| (u32::MAX as usize) < i) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "columns beyond u32 for bin"));
+    }
+    wtr.write_all(BIN_MAGIC)?;
+    for &n in &[features.len(), dim, data.len()] {
+        wtr.write_all(&(n as u64).to_le_bytes())?;
+    }
+    for &label in labels {
+        wtr.write_all(&(label as u64).to_le_bytes())?;
+    }
+    for &i in &indptr {
+        wtr.write_all(&(i as u64).to_le_bytes())?;
+    }
+    for &i in &indices {
+        wtr.write_all(&(i as u32).to_le_bytes())?;
+    }
+    for &x in &data {
+        wtr.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn read_bin<R>(mut rdr: R) -> Result<(Vec<usize>, Vec<SparseVec<f32>>), LoadError>
+    where R: Read
+{
+    let mut magic = [0; 8];
+    rdr.read_exact(&mut magic)?;
+    if magic != BIN_MAGIC {
+        return Err(FormatError::new("not a bin file").into());
+    }
+    let sizes = read_u64s(&mut rdr, 3)?;
+    let (rows, cols, nnz) = (sizes[0], sizes[1], sizes[2]);
+    let labels = read_u64s(&mut rdr, rows)?;
+    let ptrs = rows.checked_add(1).ok_or_else(|| FormatError::new("too many rows"))?;
+    let indptr = read_u64s(&mut rdr, ptrs)?;
+    let indices = read_bytes(&mut rdr, nnz, 4)?
+        .chunks(4)
+        .map(|b| <i32 as NpyElem>::from_le(b) as u32 as usize)
+        .collect::<Vec<_>>();
+    if indices.iter().any(|&i| cols <= i) {
+        return Err(FormatError::new("column out of range").into());
+    }
+    let data = read_bytes(&mut rdr, nnz, 4)?
+        .chunks(4)
+        .map(<f32 as NpyElem>::from_le)
+        .collect::<Vec<_>>();
+    Ok((labels, from_csr(&data, &indices, &indptr)?))
+}
+
+fn read_u64s<R>(rdr: &mut R, n: usize) -> Result<Vec<usize>, LoadError>
+    where R: Read
+{
+    Ok(read_bytes(rdr, n, 8)?.chunks(8).map(|b| <i64 as NpyElem>::from_le(b) as usize).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::remove_file;
+    use std::process;
+
+    fn sample() -> (Vec<usize>, Vec<SparseVec<f32>>) {
+        let features = vec![vec![(0, 0.5), (3, -1.25)].into_iter().collect(),
+                            SparseVec::new(),
+                            vec![(2, 2.0)].into_iter().collect()];
+        (vec![1, 0, 2], features)
+    }
+
+    /// the files written for `format` at `path`.
+    fn paths(format: Format, path: &str) -> Vec<String> {
+        match format {
+            Format::Mtx => vec![path.to_owned(), format!("{}.labels", path)],
+            Format::Npy => {
+                ["data", "indices", "indptr", "labels"]
+                    .iter()
+                    .map(|part| format!("{}.{}.npy", path, part))
+                    .collect()
+            }
+            _ => vec![path.to_owned()],
+        }
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let (labels, features) = sample();
+        for &format in &[Format::Svm, Format::Vw, Format::Mtx, Format::Npy, Format::Bin] {
+            let path = env::temp_dir()
+                .join(format!("doc-class-test-{}.{}", process::id(), format.name()));
+            let path = path.to_str().unwrap();
+            write_features(format, path, &labels, &features, 4).unwrap();
+            let read = read_features(format, path);
+            for path in paths(format, path) {
+                remove_file(path).unwrap()
+            }
+            assert_eq!(read.unwrap(), (labels.clone(), features.clone()), "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn multi_svm_round_trips() {
+        let (_, features) = sample();
+        let label_sets = vec![vec![0, 2], Vec::new(), vec![1]];
+        let mut bytes = Vec::new();
+        write_multi_svm(&mut bytes, &label_sets, &features).unwrap();
+        assert_eq!(read_multi_svm(&bytes[..]).unwrap(), (label_sets, features));
+    }
+
+    #[test]
+    fn csr_round_trips() {
+        let (_, features) = sample();
+        let (data, indices, indptr) = to_csr(&features);
+        assert_eq!(indptr, [0, 2, 2, 3]);
+        assert_eq!(from_csr(&data, &indices, &indptr).unwrap(), features);
+    }
+
+    fn bin_header(rows: u64, cols: u64, nnz: u64) -> Vec<u8> {
+        let mut bytes = BIN_MAGIC.to_vec();
+        for n in &[rows, cols, nnz] {
+            bytes.extend_from_slice(&n.to_le_bytes())
+        }
+        bytes
+    }
+
+    #[test]
+    fn from_csr_rejects_bad_indptr() {
+        let (data, indices) = ([1.0, 2.0], [0, 1]);
+        assert!(from_csr(&data, &indices, &[0, 2, 1, 2]).is_err());
+        assert!(from_csr(&data, &indices, &[0, 3]).is_err());
+        assert!(from_csr(&data, &indices, &[1, 2]).is_err());
+        assert!(from_csr(&data, &indices, &[]).is_err());
+        assert_eq!(from_csr(&data, &indices, &[0, 0, 2]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn read_bin_rejects_huge_sizes() {
+        assert!(read_bin(&bin_header(u64::MAX, 1, 0)[..]).is_err());
+        assert!(read_bin(&bin_header(1 << 40, 1, 0)[..]).is_err());
+        assert!(read_bin(&bin_header(0, 1, 1 << 60)[..]).is_err());
+    }
+
+    #[test]
+    fn read_bin_rejects_columns_out_of_range() {
+        let mut bytes = bin_header(1, 2, 1);
+        for n in &[0u64, 0, 1] {
+            bytes.extend_from_slice(&n.to_le_bytes())
+        }
+        bytes.extend_from_slice(&5u32.to_le_bytes());
+        bytes.extend_from_slice(&1.0f32.to_le_bytes());
+        assert!(read_bin(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn read_npy_rejects_huge_sizes() {
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &[1.0f32, 2.0]).unwrap();
+        let header_end = 10 + u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = String::from_utf8(bytes[10..header_end].to_vec()).unwrap();
+        let header = header.replacen("(2,)", "(9223372036854775807,)", 1);
+        let mut corrupt = bytes[..8].to_vec();
+        corrupt.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        corrupt.extend_from_slice(header.as_bytes());
+        corrupt.extend_from_slice(&bytes[header_end..]);
+        assert!(read_npy::<_, f32>(&corrupt[..]).is_err());
+        assert!(read_npy::<_, f32>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read_npy::<_, i64>(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn write_bin_refuses_wide_columns() {
+        let features = vec![vec![(1 << 33, 1.0)].into_iter().collect()];
+        assert!(write_bin(&mut Vec::new(), &[0], &features, 1 << 34).is_err());
+    }
+}
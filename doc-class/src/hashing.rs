@@ -0,0 +1,166 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+/// hash functions with fixed algorithms, so that hashed features stay the same
+/// across platforms and rust releases.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum HashAlgorithm {
+    /// 64-bit FNV-1a, with the seed xor-ed into the offset basis.
+    Fnv1a,
+    /// 32-bit MurmurHash3 for x86.
+    Murmur3,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HashAlgorithm::Fnv1a => "fnv",
+            HashAlgorithm::Murmur3 => "murmur3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HashAlgorithm> {
+        match name {
+            "fnv" => Some(HashAlgorithm::Fnv1a),
+            "murmur3" => Some(HashAlgorithm::Murmur3),
+            _ => None,
+        }
+    }
+
+    pub fn hash(&self, bytes: &[u8], seed: u32) -> u64 {
+        match *self {
+            HashAlgorithm::Fnv1a => fnv1a(bytes, seed),
+            HashAlgorithm::Murmur3 => murmur3(bytes, seed) as u64,
+        }
+    }
+}
+
+/// maps ngrams to dimensions. in the signed mode, each ngram also gets a sign
+/// from a second hash, so that collisions cancel out in expectation.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct FeatureHasher {
+    algorithm: HashAlgorithm,
+    seed: u32,
+    signed: bool,
+}
+
+impl Default for FeatureHasher {
+    fn default() -> Self {
+        FeatureHasher::new(HashAlgorithm::Murmur3, 0, false)
+    }
+}
+
+impl FeatureHasher {
+    pub fn new(algorithm: HashAlgorithm, seed: u32, signed: bool) -> Self {
+        FeatureHasher {
+            algorithm,
+            seed,
+            signed,
+        }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// hashes the tokens in `ngram`, each terminated by `0xff`, which never
+    /// occurs in utf-8.
+    ///
+    /// # example
+    /// ```
+    /// use doc_class::hashing::{FeatureHasher, HashAlgorithm};
+    /// let hasher = FeatureHasher::new(HashAlgorithm::Murmur3, 0, false);
+    /// assert_eq!(hasher.hash(&["a", "bc"]), HashAlgorithm::Murmur3.hash(b"a\xffbc\xff", 0));
+    /// assert!(hasher.hash(&["a", "bc"]) != hasher.hash(&["ab", "c"]));
+    /// ```
+    pub fn hash<S>(&self, ngram: &[S]) -> u64
+        where S: AsRef<str>
+    {
+        self.algorithm.hash(&ngram_bytes(ngram), self.seed)
+    }
+
+    /// returns the dimension under `dim` for `ngram` and its sign, which is
+    /// always `1` unless in the signed mode.
+    pub fn index<S>(&self, ngram: &[S], dim: usize) -> (usize, isize)
+        where S: AsRef<str>
+    {
+        let bytes = ngram_bytes(ngram);
+        let i = (self.algorithm.hash(&bytes, self.seed) % dim as u64) as usize;
+        if self.signed && self.algorithm.hash(&bytes, self.seed.wrapping_add(1)) & 1 == 1 {
+            (i, -1)
+        } else {
+            (i, 1)
+        }
+    }
+}
+
+fn ngram_bytes<S>(ngram: &[S]) -> Vec<u8>
+    where S: AsRef<str>
+{
+    let mut bytes = Vec::new();
+    for token in ngram {
+        bytes.extend_from_slice(token.as_ref().as_bytes());
+        bytes.push(0xff);
+    }
+    bytes
+}
+
+/// # example
+/// ```
+/// use doc_class::hashing::fnv1a;
+/// assert_eq!(fnv1a(b"", 0), 0xcbf29ce484222325);
+/// assert_eq!(fnv1a(b"a", 0), 0xaf63dc4c8601ec8c);
+/// assert_eq!(fnv1a(b"foobar", 0), 0x85944171f73967e8);
+/// ```
+pub fn fnv1a(bytes: &[u8], seed: u32) -> u64 {
+    let mut h = 0xcbf2_9ce4_8422_2325 ^ seed as u64;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h
+}
+
+/// # example
+/// ```
+/// use doc_class::hashing::murmur3;
+/// assert_eq!(murmur3(b"", 0), 0);
+/// assert_eq!(murmur3(b"", 1), 0x514e28b7);
+/// assert_eq!(murmur3(b"hello", 0), 0x248bfa47);
+/// assert_eq!(murmur3(b"The quick brown fox jumps over the lazy dog", 0x9747b28c), 0x2fa826cd);
+/// ```
+pub fn murmur3(bytes: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mut h = seed;
+    let mut blocks = bytes.chunks_exact(4);
+    for block in &mut blocks {
+        let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0;
+        for (i, &b) in tail.iter().enumerate() {
+            k ^= (b as u32) << (8 * i);
+        }
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+    h ^= bytes.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
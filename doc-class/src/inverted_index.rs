@@ -1,12 +1,30 @@
 // Author: Kuan Yu, 3913893
 // Honor Code:  I pledge that this program represents my own work.
 
-use sparse_vec::{SparseVec, scale_to_unit};
+use sparse_vec::SparseVec;
+use weighting::{Weighting, TermScope, Norm};
 use std::collections::HashMap;
 use protocoll::MapMut;
 
-pub type PostingsList = SparseVec<usize>;
+/// maps documents to term frequencies, which are negative for terms which
+/// were mostly inserted with a negative sign.
+pub type PostingsList = SparseVec<isize>;
 
+/// what a vectorizer needs to know about the terms of an index: the terms
+/// with at least `min_freq` in ascending order, with their document
+/// frequencies, and their document frequencies in each class if asked for,
+/// together with the length of each document over these terms.
+#[derive(Debug,Default,Clone,PartialEq)]
+pub struct TermStats {
+    pub doc_count: usize,
+    pub min_freq: usize,
+    pub terms: Vec<usize>,
+    pub doc_freqs: Vec<usize>,
+    pub class_dfs: Vec<Vec<usize>>,
+    pub doc_lens: Vec<usize>,
+}
+
+#[derive(Debug,Default,Clone)]
 pub struct InvertedIndex {
     inv_idx: HashMap<usize, PostingsList>,
     doc_count: usize,
@@ -21,53 +39,199 @@ impl InvertedIndex {
     }
 
     pub fn inv_insert(&mut self, doc: usize, term: usize) {
+        self.inv_add(doc, term, 1)
+    }
+
+    /// adds `n` to the frequency of `term` in `doc`; terms whose frequency
+    /// comes back to zero are removed from the postings list.
+    pub fn inv_add(&mut self, doc: usize, term: usize, n: isize) {
         if self.doc_count <= doc {
             self.doc_count = doc + 1
         }
-        self.inv_idx
-            .entry(term)
-            .or_insert(PostingsList::new())
-            .update_mut(doc, 0, |n| *n += 1)
+        let doc2tf = self.inv_idx.entry(term).or_default();
+        doc2tf.update_mut(doc, 0, |tf| *tf += n);
+        if doc2tf.get(&doc) == Some(&0) {
+            doc2tf.remove(&doc);
+        }
     }
 
+    /// inserts a new document with `terms`.
     pub fn inv_push<I>(&mut self, terms: I)
         where I: Iterator<Item = usize>
+    {
+        self.inv_push_signed(terms.map(|term| (term, 1)))
+    }
+
+    /// inserts a new document with `(term, sign)` pairs, as produced by a
+    /// `FeatureHasher`.
+    pub fn inv_push_signed<I>(&mut self, terms: I)
+        where I: Iterator<Item = (usize, isize)>
     {
         let doc = self.doc_count;
-        for term in terms {
-            self.inv_insert(doc, term)
+        self.doc_count += 1;
+        for (term, sign) in terms {
+            self.inv_add(doc, term, sign)
         }
     }
 
-    /// returns a document feature matrix;
-    /// `feat_fn` shoud compute a feature from `(term_freq, doc_freq, doc_count)`;
-    /// terms under `min_freq` are ignored.
-    pub fn doc_features(&self,
-                        feat_fn: fn(usize, usize, usize) -> f32,
-                        min_freq: usize)
-                        -> Vec<SparseVec<f32>> {
-        let dc = self.doc_count;
-        let mut feat_mat = Vec::new();
-        feat_mat.resize(dc, SparseVec::new());
-        let mut dim = 0;
+    /// appends the documents of `other` after those in `self`, numbering
+    /// them on from `doc_count`, so that indices built in parts over
+    /// consecutive documents merge into the same index as built at once.
+    pub fn append(&mut self, other: InvertedIndex) {
+        let offset = self.doc_count;
+        for (term, doc2tf) in other.inv_idx {
+            let postings = self.inv_idx.entry(term).or_default();
+            for (doc, tf) in doc2tf {
+                postings.insert(offset + doc, tf);
+            }
+        }
+        self.doc_count += other.doc_count;
+    }
+
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    pub fn view_content(&self) -> &HashMap<usize, PostingsList> {
+        &self.inv_idx
+    }
+
+    /// removes all terms not satisfying `pred`.
+    pub fn retain_terms<F>(&mut self, mut pred: F)
+        where F: FnMut(usize) -> bool
+    {
+        self.inv_idx.retain(|&term, _| pred(term))
+    }
+
+    /// returns `(term, doc_freq)` for all terms with at least `min_freq`,
+    /// sorted by term.
+    pub fn doc_freqs(&self, min_freq: usize) -> Vec<(usize, usize)> {
+        let mut term2df = self.inv_idx
+            .iter()
+            .map(|(&term, doc2tf)| (term, doc2tf.len()))
+            .filter(|&(_, df)| 0 < df && min_freq <= df)
+            .collect::<Vec<_>>();
+        term2df.sort();
+        term2df
+    }
+
+    /// returns the sum of the absolute term frequencies in each document,
+    /// over the terms with at least `min_freq`.
+    pub fn doc_lens(&self, min_freq: usize) -> Vec<usize> {
+        let mut doc_lens = vec![0; self.doc_count];
         for doc2tf in self.inv_idx.values() {
-            let df = doc2tf.len();
-            if df < min_freq {
+            if doc2tf.is_empty() || doc2tf.len() < min_freq {
                 continue;
             }
             for &(doc, tf) in doc2tf {
-                feat_mat[doc].insert(dim, feat_fn(tf, df, dc));
+                doc_lens[doc] += tf.unsigned_abs()
+            }
+        }
+        doc_lens
+    }
+
+    /// returns the document frequency of `term` in each class, where `labels`
+    /// are the classes of the documents numbered from `0`.
+    pub fn class_doc_freqs(&self, term: usize, labels: &[usize], class_count: usize) -> Vec<usize> {
+        let mut class_dfs = vec![0; class_count];
+        if let Some(doc2tf) = self.inv_idx.get(&term) {
+            for &(doc, _) in doc2tf {
+                class_dfs[labels[doc]] += 1
+            }
+        }
+        class_dfs
+    }
+
+    /// returns the statistics of the terms with at least `min_freq`, with
+    /// their document frequencies in each of `class_count` classes when
+    /// `with_class_dfs`, where `labels` are the classes of the documents.
+    pub fn term_stats(&self,
+                      labels: &[usize],
+                      class_count: usize,
+                      min_freq: usize,
+                      with_class_dfs: bool)
+                      -> TermStats {
+        let (terms, doc_freqs): (Vec<_>, Vec<_>) = self.doc_freqs(min_freq).into_iter().unzip();
+        let class_dfs = if with_class_dfs {
+            terms.iter().map(|&term| self.class_doc_freqs(term, labels, class_count)).collect()
+        } else {
+            Vec::new()
+        };
+        TermStats {
+            doc_count: self.doc_count,
+            min_freq,
+            terms,
+            doc_freqs,
+            class_dfs,
+            doc_lens: self.doc_lens(min_freq),
+        }
+    }
+
+    /// returns a document feature matrix, with features from `weighting`
+    /// scaled by `norm`; terms under `min_freq` are ignored. the remaining
+    /// terms are numbered in ascending order, as in `doc_freqs`. negative
+    /// frequencies give negative features. `labels` are the classes of the
+    /// documents numbered from `0`, only needed when the weighting
+    /// `needs_classes`.
+    pub fn doc_features<W>(&self,
+                           weighting: &W,
+                           labels: &[usize],
+                           norm: Norm,
+                           min_freq: usize)
+                           -> Vec<SparseVec<f32>>
+        where W: Weighting + ?Sized
+    {
+        let dc = self.doc_count;
+        let doc_lens = self.doc_lens(min_freq);
+        let avg_doc_len = mean(&doc_lens);
+        let class_sizes = class_sizes(labels);
+        let mut feat_mat = Vec::new();
+        feat_mat.resize(dc, SparseVec::new());
+        let mut dim = 0;
+        for (term, df) in self.doc_freqs(min_freq) {
+            let class_dfs = if weighting.needs_classes() {
+                self.class_doc_freqs(term, labels, class_sizes.len())
+            } else {
+                Vec::new()
+            };
+            let scope = TermScope {
+                avg_doc_len,
+                df,
+                doc_count: dc,
+                class_dfs: &class_dfs,
+                class_sizes: &class_sizes,
+            };
+            for &(doc, tf) in &self.inv_idx[&term] {
+                feat_mat[doc].insert(dim, scope.signed_weight(weighting, tf, doc_lens[doc]));
             }
             dim += 1;
         }
         for feat_vec in &mut feat_mat {
-            scale_to_unit(feat_vec)
+            norm.apply(feat_vec)
         }
         println!("actual total dim: {}", dim);
         feat_mat
     }
 }
 
+/// the mean of `xs`, or `0` for none.
+pub fn mean(xs: &[usize]) -> f32 {
+    if xs.is_empty() {
+        0.0
+    } else {
+        xs.iter().sum::<usize>() as f32 / xs.len() as f32
+    }
+}
+
+/// the number of documents in each class, from `labels` numbered from `0`.
+pub fn class_sizes(labels: &[usize]) -> Vec<usize> {
+    let mut class_sizes = vec![0; labels.iter().max().map_or(0, |&l| l + 1)];
+    for &label in labels {
+        class_sizes[label] += 1
+    }
+    class_sizes
+}
+
 pub fn binary(tf: usize, _: usize, _: usize) -> f32 {
     if tf > 0 { 1.0 } else { 0.0 }
 }
@@ -46,6 +46,7 @@ pub fn iter_file_paths<P>(dir: P) -> FilePaths
 }
 
 /// returns an iterator which recursively list all `PathBuf` of files under `dir`.
+#[allow(deprecated)]
 pub fn file_paths<P>(dir: P) -> io::Result<FilePaths>
     where P: AsRef<Path>
 {
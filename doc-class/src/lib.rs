@@ -1,10 +1,29 @@
 // Author: Kuan Yu, 3913893
 // Honor Code:  I pledge that this program represents my own work.
 
+extern crate conllx;
 extern crate protocoll;
+extern crate rust2vec;
 
+pub mod blocked_index;
+pub mod corpus;
+pub mod csr_matrix;
+pub mod embedding;
+pub mod dictionary;
+pub mod error;
+pub mod evaluation;
+pub mod extraction;
 pub mod filters;
+pub mod formats;
+pub mod hashing;
 pub mod io_utils;
 pub mod numberer;
 pub mod sparse_vec;
 pub mod inverted_index;
+pub mod linear_model;
+pub mod multi_label;
+pub mod naive_bayes;
+pub mod selection;
+pub mod vectorizer;
+pub mod vector_space;
+pub mod weighting;
@@ -0,0 +1,520 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::{SparseVec, argmax};
+use error::{LoadError, FormatError};
+use std::io::{self, BufRead, Write};
+
+/// the most passes of coordinate descent over the data, as in liblinear.
+pub const MAX_ITER: usize = 1000;
+
+/// l2-regularized linear solvers, all optimized in the dual by coordinate
+/// descent, following liblinear (Hsieh et al. 2008; Yu et al. 2011).
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Solver {
+    /// logistic regression; `-s 7` in liblinear.
+    LogReg,
+    /// svm with squared hinge loss; `-s 1` in liblinear.
+    L2LossSvm,
+    /// svm with hinge loss; `-s 3` in liblinear.
+    L1LossSvm,
+}
+
+impl Solver {
+    /// the name used by liblinear in its model files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Solver::LogReg => "L2R_LR_DUAL",
+            Solver::L2LossSvm => "L2R_L2LOSS_SVC_DUAL",
+            Solver::L1LossSvm => "L2R_L1LOSS_SVC_DUAL",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Solver> {
+        match name {
+            "L2R_LR_DUAL" => Some(Solver::LogReg),
+            "L2R_L2LOSS_SVC_DUAL" => Some(Solver::L2LossSvm),
+            "L2R_L1LOSS_SVC_DUAL" => Some(Solver::L1LossSvm),
+            _ => None,
+        }
+    }
+}
+
+/// a one-vs-rest linear classifier. with only two classes, a single weight
+/// vector is kept for the first label, as liblinear does, so that saved models
+/// can also be read by `liblinear-predict`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct LinearModel {
+    solver: Solver,
+    labels: Vec<usize>,
+    dim: usize,
+    bias: f32,
+    weights: Vec<Vec<f32>>,
+    /// the passes taken by the solver for each weight vector.
+    iterations: Vec<usize>,
+}
+
+impl LinearModel {
+    /// fits a model on `features` with `labels`; `cost` is the penalty for
+    /// the loss, `bias` the value for an extra constant feature (none when
+    /// negative), and `eps` the tolerance for stopping.
+    pub fn train(solver: Solver,
+                 features: &[SparseVec<f32>],
+                 labels: &[usize],
+                 cost: f32,
+                 bias: f32,
+                 eps: f32)
+                 -> Self {
+        let mut classes = Vec::new();
+        for &label in labels {
+            if !classes.contains(&label) {
+                classes.push(label)
+            }
+        }
+        let dim = features.iter()
+            .filter_map(|x| x.view_content().last().map(|&(i, _)| i + 1))
+            .max()
+            .unwrap_or(0);
+        let targets = if classes.len() == 2 {
+            &classes[..1]
+        } else {
+            &classes[..]
+        };
+        let (weights, iterations) = targets.iter()
+            .map(|&target| {
+                let y = labels.iter()
+                    .map(|&label| if label == target { 1.0 } else { -1.0 })
+                    .collect::<Vec<_>>();
+                let (w, iter) = match solver {
+                    Solver::LogReg => solve_lr_dual(features, &y, dim, cost, bias, eps),
+                    Solver::L2LossSvm => solve_svm_dual(features, &y, dim, cost, bias, eps, false),
+                    Solver::L1LossSvm => solve_svm_dual(features, &y, dim, cost, bias, eps, true),
+                };
+                (w.into_iter().map(|x| x as f32).collect(), iter)
+            })
+            .unzip();
+        LinearModel {
+            solver,
+            labels: classes,
+            dim,
+            bias,
+            weights,
+            iterations,
+        }
+    }
+
+    pub fn solver(&self) -> Solver {
+        self.solver
+    }
+
+    pub fn labels(&self) -> &[usize] {
+        &self.labels
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    /// one weight vector for each class, or a single one for the first label
+    /// in a binary model; the bias weight comes last when `bias >= 0`.
+    pub fn weights(&self) -> &[Vec<f32>] {
+        &self.weights
+    }
+
+    /// the passes of coordinate descent taken for each weight vector; empty
+    /// for a loaded model.
+    pub fn iterations(&self) -> &[usize] {
+        &self.iterations
+    }
+
+    /// whether the solver stopped before `MAX_ITER` for every weight vector,
+    /// as liblinear warns otherwise.
+    pub fn converged(&self) -> bool {
+        self.iterations.iter().all(|&iter| iter < MAX_ITER)
+    }
+
+    /// returns each label with its weights over the features, without the
+    /// bias; in a binary model, the second label gets the negated weights of
+    /// the first.
+    pub fn class_weights(&self) -> Vec<(usize, Vec<f32>)> {
+        let mut class_weights = self.labels
+            .iter()
+            .zip(&self.weights)
+            .map(|(&label, w)| (label, w[..self.dim].to_vec()))
+            .collect::<Vec<_>>();
+        if self.weights.len() == 1 && self.labels.len() == 2 {
+            let w = self.weights[0][..self.dim].iter().map(|w| -w).collect();
+            class_weights.push((self.labels[1], w))
+        }
+        class_weights
+    }
+
+    /// returns one decision value for each weight vector.
+    pub fn decision_values(&self, x: &SparseVec<f32>) -> Vec<f32> {
+        self.weights
+            .iter()
+            .map(|w| {
+                let mut dec = 0.0;
+                for &(i, v) in x {
+                    if i < self.dim {
+                        dec += w[i] * v
+                    }
+                }
+                if self.bias >= 0.0 {
+                    dec += w[self.dim] * self.bias
+                }
+                dec
+            })
+            .collect()
+    }
+
+    /// returns the only label of a model trained on a single class.
+    pub fn predict(&self, x: &SparseVec<f32>) -> usize {
+        if self.labels.len() == 1 {
+            return self.labels[0];
+        }
+        let decs = self.decision_values(x);
+        if self.weights.len() == 1 {
+            if decs[0] > 0.0 {
+                self.labels[0]
+            } else {
+                self.labels[1]
+            }
+        } else {
+            self.labels[argmax(&decs)]
+        }
+    }
+
+    /// writes the model in the text format of liblinear.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "solver_type {}", self.solver.name())?;
+        writeln!(wtr, "nr_class {}", self.labels.len())?;
+        write!(wtr, "label")?;
+        for label in &self.labels {
+            write!(wtr, " {}", label)?;
+        }
+        writeln!(wtr)?;
+        writeln!(wtr, "nr_feature {}", self.dim)?;
+        writeln!(wtr, "bias {}", self.bias)?;
+        writeln!(wtr, "w")?;
+        let rows = if self.bias >= 0.0 { self.dim + 1 } else { self.dim };
+        for i in 0..rows {
+            for w in &self.weights {
+                write!(wtr, "{} ", w[i])?;
+            }
+            writeln!(wtr)?;
+        }
+        Ok(())
+    }
+
+    /// reads a model written by `save` or by liblinear with one of the
+    /// supported solvers.
+    pub fn load<R>(rdr: R) -> Result<LinearModel, LoadError>
+        where R: BufRead
+    {
+        let mut lines = rdr.lines();
+        let mut solver = None;
+        let mut labels = Vec::new();
+        let mut dim = 0;
+        let mut bias = -1.0;
+        loop {
+            let line = match lines.next() {
+                Some(res_line) => res_line?,
+                None => return Err(FormatError::new("missing weights").into()),
+            };
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("solver_type") => {
+                    let name = fields.next().unwrap_or("");
+                    solver = Some(Solver::from_name(name).ok_or_else(|| FormatError::new(&line))?)
+                }
+                Some("nr_class") => {}
+                Some("label") => {
+                    for label in fields {
+                        labels.push(label.parse()?)
+                    }
+                }
+                Some("nr_feature") => dim = fields.next().unwrap_or("").parse()?,
+                Some("bias") => bias = fields.next().unwrap_or("").parse()?,
+                Some("w") => break,
+                _ => return Err(FormatError::new(&line).into()),
+            }
+        }
+        let solver = solver.ok_or_else(|| FormatError::new("missing solver_type"))?;
+        let nr_w = if labels.len() == 2 { 1 } else { labels.len() };
+        let rows = if bias >= 0.0 { dim + 1 } else { dim };
+        let mut weights = vec![Vec::with_capacity(rows); nr_w];
+        for _ in 0..rows {
+            let line = match lines.next() {
+                Some(res_line) => res_line?,
+                None => return Err(FormatError::new("missing weights").into()),
+            };
+            let mut fields = line.split_whitespace();
+            for w in &mut weights {
+                w.push(fields.next().ok_or_else(|| FormatError::new(&line))?.parse()?)
+            }
+        }
+        Ok(LinearModel {
+            solver,
+            labels,
+            dim,
+            bias,
+            weights,
+            iterations: Vec::new(),
+        })
+    }
+}
+
+/// a xorshift generator, only for shuffling the order of coordinates.
+struct Shuffler(u64);
+
+impl Shuffler {
+    fn shuffle(&mut self, perm: &mut [usize]) {
+        for i in (1..perm.len()).rev() {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            perm.swap(i, (self.0 % (i as u64 + 1)) as usize);
+        }
+    }
+}
+
+fn dot(w: &[f64], x: &SparseVec<f32>, dim: usize, bias: f32) -> f64 {
+    let mut ret = 0.0;
+    for &(i, v) in x {
+        ret += w[i] * v as f64
+    }
+    if bias >= 0.0 {
+        ret += w[dim] * bias as f64
+    }
+    ret
+}
+
+fn axpy(a: f64, x: &SparseVec<f32>, w: &mut [f64], dim: usize, bias: f32) {
+    for &(i, v) in x {
+        w[i] += a * v as f64
+    }
+    if bias >= 0.0 {
+        w[dim] += a * bias as f64
+    }
+}
+
+fn sq_norm(x: &SparseVec<f32>, bias: f32) -> f64 {
+    let mut ret = 0.0;
+    for &(_, v) in x {
+        ret += v as f64 * v as f64
+    }
+    if bias >= 0.0 {
+        ret += bias as f64 * bias as f64
+    }
+    ret
+}
+
+fn solve_svm_dual(xs: &[SparseVec<f32>],
+                  ys: &[f64],
+                  dim: usize,
+                  cost: f32,
+                  bias: f32,
+                  eps: f32,
+                  hinge: bool)
+                  -> (Vec<f64>, usize) {
+    let (upper, diag) = if hinge {
+        (cost as f64, 0.0)
+    } else {
+        (f64::INFINITY, 0.5 / cost as f64)
+    };
+    let mut w = vec![0.0; dim + 1];
+    let mut alpha = vec![0.0; xs.len()];
+    let qd = xs.iter().map(|x| sq_norm(x, bias) + diag).collect::<Vec<_>>();
+    let mut perm = (0..xs.len()).collect::<Vec<_>>();
+    let mut shuffler = Shuffler(0x2545_f491_4f6c_dd1d);
+    let mut iter = 0;
+    while iter < MAX_ITER {
+        iter += 1;
+        shuffler.shuffle(&mut perm);
+        let mut pg_max = f64::NEG_INFINITY;
+        let mut pg_min = f64::INFINITY;
+        for &i in &perm {
+            let g = ys[i] * dot(&w, &xs[i], dim, bias) - 1.0 + diag * alpha[i];
+            let pg = if alpha[i] == 0.0 {
+                g.min(0.0)
+            } else if alpha[i] == upper {
+                g.max(0.0)
+            } else {
+                g
+            };
+            pg_max = pg_max.max(pg);
+            pg_min = pg_min.min(pg);
+            if pg.abs() > 1e-12 {
+                let alpha_old = alpha[i];
+                alpha[i] = (alpha[i] - g / qd[i]).max(0.0).min(upper);
+                axpy((alpha[i] - alpha_old) * ys[i], &xs[i], &mut w, dim, bias);
+            }
+        }
+        if pg_max - pg_min <= eps as f64 {
+            break;
+        }
+    }
+    (w, iter)
+}
+
+fn solve_lr_dual(xs: &[SparseVec<f32>],
+                 ys: &[f64],
+                 dim: usize,
+                 cost: f32,
+                 bias: f32,
+                 eps: f32)
+                 -> (Vec<f64>, usize) {
+    // every `alpha[i]` is paired with its complement `cost - alpha[i]`, and the
+    // newton steps are taken on whichever of the two is further from `cost`.
+    let c = cost as f64;
+    let max_inner_iter = 100;
+    let inner_eps_min = f64::min(1e-8, eps as f64);
+    let mut inner_eps = 1e-2;
+    let mut w = vec![0.0; dim + 1];
+    let mut alpha = vec![0.0; 2 * xs.len()];
+    for (i, x) in xs.iter().enumerate() {
+        alpha[2 * i] = f64::min(0.001 * c, 1e-8);
+        alpha[2 * i + 1] = c - alpha[2 * i];
+        axpy(ys[i] * alpha[2 * i], x, &mut w, dim, bias);
+    }
+    let qd = xs.iter().map(|x| sq_norm(x, bias)).collect::<Vec<_>>();
+    let mut perm = (0..xs.len()).collect::<Vec<_>>();
+    let mut shuffler = Shuffler(0x2545_f491_4f6c_dd1d);
+    let mut iter = 0;
+    while iter < MAX_ITER {
+        iter += 1;
+        shuffler.shuffle(&mut perm);
+        let mut g_max: f64 = 0.0;
+        for &i in &perm {
+            let a = qd[i];
+            let b = ys[i] * dot(&w, &xs[i], dim, bias);
+            let (ind1, ind2, sign) = if 0.5 * a * (alpha[2 * i + 1] - alpha[2 * i]) + b < 0.0 {
+                (2 * i + 1, 2 * i, -1.0)
+            } else {
+                (2 * i, 2 * i + 1, 1.0)
+            };
+            let alpha_old = alpha[ind1];
+            let mut z = alpha_old;
+            if c - z < 0.5 * c {
+                z *= 0.1
+            }
+            let mut gp = a * (z - alpha_old) + sign * b + f64::ln(z / (c - z));
+            g_max = g_max.max(gp.abs());
+            let mut inner_iter = 0;
+            while inner_iter <= max_inner_iter {
+                if gp.abs() < inner_eps {
+                    break;
+                }
+                let gpp = a + c / (c - z) / z;
+                let tmp_z = z - gp / gpp;
+                if tmp_z <= 0.0 {
+                    z *= 0.1
+                } else {
+                    z = tmp_z
+                }
+                gp = a * (z - alpha_old) + sign * b + f64::ln(z / (c - z));
+                inner_iter += 1;
+            }
+            if inner_iter > 0 {
+                alpha[ind1] = z;
+                alpha[ind2] = c - z;
+                axpy(sign * (z - alpha_old) * ys[i], &xs[i], &mut w, dim, bias);
+            }
+        }
+        if g_max < eps as f64 {
+            break;
+        }
+        if g_max < inner_eps {
+            inner_eps = f64::max(inner_eps_min, 0.1 * inner_eps);
+        }
+    }
+    (w, iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// two classes on either side of the diagonal.
+    fn toy_set() -> (Vec<SparseVec<f32>>, Vec<usize>) {
+        let points = [(1.0, 0.1, 0),
+                      (0.9, 0.2, 0),
+                      (0.8, 0.0, 0),
+                      (0.1, 1.0, 1),
+                      (0.2, 0.9, 1),
+                      (0.0, 0.8, 1)];
+        let features = points.iter()
+            .map(|&(x, y, _)| vec![(0, x), (1, y)].into_iter().collect())
+            .collect();
+        let labels = points.iter().map(|&(_, _, label)| label).collect();
+        (features, labels)
+    }
+
+    #[test]
+    fn solvers_separate_a_separable_set() {
+        let (features, labels) = toy_set();
+        for &solver in &[Solver::LogReg, Solver::L2LossSvm, Solver::L1LossSvm] {
+            let model = LinearModel::train(solver, &features, &labels, 10.0, 1.0, 0.01);
+            assert_eq!(model.weights().len(), 1);
+            for (x, &label) in features.iter().zip(&labels) {
+                assert_eq!(model.predict(x), label, "{}", solver.name());
+            }
+        }
+    }
+
+    #[test]
+    fn multi_class_model_keeps_a_weight_vector_per_class() {
+        let (mut features, mut labels) = toy_set();
+        for &(x, label) in &[(-1.0, 2), (-0.9, 2), (-0.8, 2)] {
+            features.push(vec![(0, x), (1, x)].into_iter().collect());
+            labels.push(label);
+        }
+        let model = LinearModel::train(Solver::L2LossSvm, &features, &labels, 10.0, 1.0, 0.01);
+        assert_eq!(model.weights().len(), 3);
+        for (x, &label) in features.iter().zip(&labels) {
+            assert_eq!(model.predict(x), label);
+        }
+    }
+
+    #[test]
+    fn single_class_model_predicts_its_label() {
+        let (features, _) = toy_set();
+        let labels = vec![4; features.len()];
+        let model = LinearModel::train(Solver::LogReg, &features, &labels, 1.0, 1.0, 0.1);
+        assert_eq!(model.predict(&features[0]), 4);
+        assert_eq!(model.predict(&SparseVec::new()), 4);
+        assert_eq!(model.class_weights().len(), 1);
+    }
+
+    #[test]
+    fn reports_reaching_the_maximum_of_iterations() {
+        let (features, labels) = toy_set();
+        for &solver in &[Solver::LogReg, Solver::L2LossSvm, Solver::L1LossSvm] {
+            let model = LinearModel::train(solver, &features, &labels, 1.0, 1.0, 0.1);
+            assert!(model.converged());
+            assert!(model.iterations()[0] < MAX_ITER);
+            // a negative tolerance is never met.
+            let model = LinearModel::train(solver, &features, &labels, 1.0, 1.0, -1.0);
+            assert!(!model.converged());
+            assert_eq!(model.iterations(), [MAX_ITER]);
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let (features, labels) = toy_set();
+        let model = LinearModel::train(Solver::L2LossSvm, &features, &labels, 1.0, 1.0, 0.1);
+        let mut buf = Vec::new();
+        model.save(&mut buf).unwrap();
+        let loaded = LinearModel::load(&buf[..]).unwrap();
+        assert_eq!(loaded.labels(), model.labels());
+        assert_eq!(loaded.predict(&features[0]), model.predict(&features[0]));
+    }
+}
@@ -0,0 +1,247 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::SparseVec;
+use linear_model::{LinearModel, Solver};
+use evaluation::split_labels;
+use error::{LoadError, FormatError};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// the labels of each document by its path, for documents with several
+/// labels, or labels not given by their directories.
+#[derive(Debug,Default,Clone,PartialEq,Eq)]
+pub struct LabelManifest {
+    path2labels: HashMap<PathBuf, Vec<String>>,
+}
+
+impl LabelManifest {
+    /// reads lines of a document path and its comma-separated labels,
+    /// separated by tab, where the first label counts as the class for
+    /// single-label purposes. empty lines are skipped.
+    pub fn read<R>(rdr: R) -> Result<LabelManifest, LoadError>
+        where R: BufRead
+    {
+        let mut path2labels = HashMap::new();
+        for res_line in rdr.lines() {
+            let line = res_line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (path, labels) = match line.find('\t') {
+                Some(x) => (&line[..x], split_labels(&line[x + 1..])),
+                None => (line.trim(), Vec::new()),
+            };
+            if labels.is_empty() {
+                return Err(FormatError::new(&format!("no labels for {}", path)).into());
+            }
+            if path2labels.insert(PathBuf::from(path), labels).is_some() {
+                return Err(FormatError::new(&format!("repeated path {}", path)).into());
+            }
+        }
+        Ok(LabelManifest { path2labels })
+    }
+
+    pub fn len(&self) -> usize {
+        self.path2labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path2labels.is_empty()
+    }
+
+    /// returns the labels of the document at `path`, which is listed either
+    /// as it is, or relative to `root`.
+    pub fn labels(&self, path: &Path, root: &Path) -> Option<&[String]> {
+        self.path2labels
+            .get(path)
+            .or_else(|| path.strip_prefix(root).ok().and_then(|p| self.path2labels.get(p)))
+            .map(Vec::as_slice)
+    }
+}
+
+/// one binary linear classifier for each class, deciding whether a document
+/// has that class, independently of the others.
+#[derive(Debug,Clone,PartialEq)]
+pub struct OneVsRest {
+    models: Vec<LinearModel>,
+}
+
+impl OneVsRest {
+    /// fits a model for each of the `class_count` classes, numbered from `0`,
+    /// on `features` with `label_sets`; the other arguments are as for
+    /// `LinearModel::train`.
+    pub fn train(solver: Solver,
+                 features: &[SparseVec<f32>],
+                 label_sets: &[Vec<usize>],
+                 class_count: usize,
+                 cost: f32,
+                 bias: f32,
+                 eps: f32)
+                 -> Self {
+        let models = (0..class_count)
+            .map(|class| {
+                let labels = label_sets.iter()
+                    .map(|labels| if labels.contains(&class) { 1 } else { 0 })
+                    .collect::<Vec<_>>();
+                LinearModel::train(solver, features, &labels, cost, bias, eps)
+            })
+            .collect();
+        OneVsRest { models }
+    }
+
+    /// the binary models, one for each class, with the label `1` for the
+    /// class and `0` for the rest.
+    pub fn models(&self) -> &[LinearModel] {
+        &self.models
+    }
+
+    /// whether the solver converged for every class; see
+    /// `LinearModel::converged`.
+    pub fn converged(&self) -> bool {
+        self.models.iter().all(LinearModel::converged)
+    }
+
+    /// returns each class with the weights of its model over the features,
+    /// without the bias; classes with only positive or only negative training
+    /// documents are left out.
+    pub fn class_weights(&self) -> Vec<(usize, Vec<f32>)> {
+        self.models
+            .iter()
+            .enumerate()
+            .filter(|&(_, model)| model.labels().len() == 2)
+            .filter_map(|(class, model)| {
+                let class_weights = model.class_weights();
+                class_weights.into_iter().find(|&(label, _)| label == 1).map(|(_, w)| (class, w))
+            })
+            .collect()
+    }
+
+    /// returns the decision value of each class, positive for predicting it.
+    /// a class with only positive or only negative training documents gets
+    /// `1` or `-1`.
+    pub fn decision_values(&self, x: &SparseVec<f32>) -> Vec<f32> {
+        self.models
+            .iter()
+            .map(|model| {
+                let labels = model.labels();
+                if labels.len() < 2 {
+                    return if labels.first() == Some(&1) { 1.0 } else { -1.0 };
+                }
+                let dec = model.decision_values(x)[0];
+                if labels[0] == 1 { dec } else { -dec }
+            })
+            .collect()
+    }
+
+    /// returns the classes with positive decision values, in ascending order.
+    pub fn predict(&self, x: &SparseVec<f32>) -> Vec<usize> {
+        self.decision_values(x)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, dec)| dec > 0.0)
+            .map(|(class, _)| class)
+            .collect()
+    }
+
+    /// writes the line `one_vs_rest K`, followed by the models in the text
+    /// format of liblinear.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "one_vs_rest {}", self.models.len())?;
+        for model in &self.models {
+            model.save(wtr)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R>(mut rdr: R) -> Result<OneVsRest, LoadError>
+        where R: BufRead
+    {
+        let mut line = String::new();
+        rdr.read_line(&mut line)?;
+        let class_count = match line.trim().split(' ').collect::<Vec<_>>().as_slice() {
+            ["one_vs_rest", count] => count.parse()?,
+            _ => return Err(FormatError::new("missing one_vs_rest").into()),
+        };
+        let mut models = Vec::with_capacity(class_count);
+        for _ in 0..class_count {
+            models.push(LinearModel::load(&mut rdr)?)
+        }
+        Ok(OneVsRest { models })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_resolves_paths_relative_to_the_root() {
+        let manifest = LabelManifest::read("docs/a.txt\tx, y\n\n/abs/b.txt\tz\n".as_bytes()).unwrap();
+        assert_eq!(manifest.len(), 2);
+        let root = Path::new("/corpus");
+        assert_eq!(manifest.labels(Path::new("/corpus/docs/a.txt"), root).unwrap(), ["x", "y"]);
+        assert_eq!(manifest.labels(Path::new("docs/a.txt"), root).unwrap(), ["x", "y"]);
+        assert_eq!(manifest.labels(Path::new("/abs/b.txt"), root).unwrap(), ["z"]);
+        assert_eq!(manifest.labels(Path::new("/corpus/b.txt"), root), None);
+        assert_eq!(manifest.labels(Path::new("/other/docs/a.txt"), root), None);
+    }
+
+    #[test]
+    fn manifest_rejects_missing_labels_and_repeated_paths() {
+        assert!(LabelManifest::read("a.txt\n".as_bytes()).is_err());
+        assert!(LabelManifest::read("a.txt\t ,\n".as_bytes()).is_err());
+        assert!(LabelManifest::read("a.txt\tx\na.txt\ty\n".as_bytes()).is_err());
+    }
+
+    /// the first feature decides class `0`, the second class `1`, and no
+    /// document has class `2`.
+    fn toy_set() -> (Vec<SparseVec<f32>>, Vec<Vec<usize>>) {
+        let points = [(1.0, 0.0, vec![0]),
+                      (0.9, 0.1, vec![0]),
+                      (0.0, 1.0, vec![1]),
+                      (0.1, 0.9, vec![1]),
+                      (1.0, 1.0, vec![0, 1]),
+                      (0.9, 0.8, vec![0, 1]),
+                      (0.0, 0.0, vec![]),
+                      (0.1, 0.1, vec![])];
+        let features = points.iter()
+            .map(|&(x, y, _)| vec![(0, x), (1, y)].into_iter().collect())
+            .collect();
+        let label_sets = points.iter().map(|(_, _, labels)| labels.clone()).collect();
+        (features, label_sets)
+    }
+
+    #[test]
+    fn one_vs_rest_separates_a_separable_set() {
+        let (features, label_sets) = toy_set();
+        let model = OneVsRest::train(Solver::L2LossSvm, &features, &label_sets, 3, 10.0, 1.0, 0.01);
+        assert_eq!(model.models().len(), 3);
+        for (x, labels) in features.iter().zip(&label_sets) {
+            assert_eq!(&model.predict(x), labels);
+            assert_eq!(model.decision_values(x)[2], -1.0);
+        }
+        let class_weights = model.class_weights();
+        assert_eq!(class_weights.iter().map(|&(class, _)| class).collect::<Vec<_>>(), [0, 1]);
+        assert!(class_weights[0].1[0] > class_weights[0].1[1]);
+        assert!(class_weights[1].1[1] > class_weights[1].1[0]);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let (features, label_sets) = toy_set();
+        let model = OneVsRest::train(Solver::LogReg, &features, &label_sets, 3, 10.0, 1.0, 0.01);
+        let mut buf = Vec::new();
+        model.save(&mut buf).unwrap();
+        let loaded = OneVsRest::load(&buf[..]).unwrap();
+        assert_eq!(loaded.models().len(), 3);
+        for x in &features {
+            assert_eq!(loaded.predict(x), model.predict(x));
+        }
+        assert!(OneVsRest::load("one_vs_rest x\n".as_bytes()).is_err());
+        assert!(OneVsRest::load(&buf[..buf.len() / 2]).is_err());
+    }
+}
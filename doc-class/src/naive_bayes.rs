@@ -0,0 +1,328 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use inverted_index::{InvertedIndex, class_sizes};
+use sparse_vec::argmax;
+use error::{LoadError, FormatError};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// how a document is generated from the term distribution of its class
+/// (McCallum & Nigam 1998).
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum EventModel {
+    /// a bag of term occurrences, so the term frequencies count.
+    Multinomial,
+    /// a binary vector of present and absent terms, so absent terms count as
+    /// well.
+    Bernoulli,
+}
+
+impl EventModel {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EventModel::Multinomial => "multinomial",
+            EventModel::Bernoulli => "bernoulli",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EventModel> {
+        match name {
+            "multinomial" => Some(EventModel::Multinomial),
+            "bernoulli" => Some(EventModel::Bernoulli),
+            _ => None,
+        }
+    }
+}
+
+/// a naive bayes classifier over the terms of an inverted index, with
+/// additive (lidstone) smoothing, where `alpha = 1` is laplace smoothing.
+/// only the counts are kept, from which the probabilities are derived.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NaiveBayes {
+    event: EventModel,
+    alpha: f32,
+    class_sizes: Vec<usize>,
+    /// for each term, its total frequency in each class for `Multinomial`,
+    /// or its document frequency in each class for `Bernoulli`.
+    term_counts: HashMap<usize, Vec<usize>>,
+    log_priors: Vec<f64>,
+    /// for each term, `ln p(t|c)` for `Multinomial`, or the log odds
+    /// `ln p(t|c) - ln (1 - p(t|c))` for `Bernoulli`.
+    log_probs: HashMap<usize, Vec<f64>>,
+    /// the score of each class before seeing any term: zero for
+    /// `Multinomial`, and the sum of `ln (1 - p(t|c))` over all terms for
+    /// `Bernoulli`.
+    base: Vec<f64>,
+}
+
+impl NaiveBayes {
+    /// counts the terms in `inv_idx` with at least `min_freq` for the classes
+    /// in `labels`, numbered from `0`. negative frequencies count by their
+    /// absolute values.
+    pub fn train(event: EventModel,
+                 inv_idx: &InvertedIndex,
+                 labels: &[usize],
+                 alpha: f32,
+                 min_freq: usize)
+                 -> Self {
+        let class_sizes = class_sizes(labels);
+        let mut term_counts = HashMap::new();
+        for (term, _) in inv_idx.doc_freqs(min_freq) {
+            let mut counts = vec![0; class_sizes.len()];
+            for &(doc, tf) in &inv_idx.view_content()[&term] {
+                counts[labels[doc]] += match event {
+                    EventModel::Multinomial => tf.unsigned_abs(),
+                    EventModel::Bernoulli => 1,
+                }
+            }
+            term_counts.insert(term, counts);
+        }
+        NaiveBayes::from_counts(event, alpha, class_sizes, term_counts)
+    }
+
+    fn from_counts(event: EventModel,
+                   alpha: f32,
+                   class_sizes: Vec<usize>,
+                   term_counts: HashMap<usize, Vec<usize>>)
+                   -> Self {
+        let a = alpha as f64;
+        let doc_count = class_sizes.iter().sum::<usize>() as f64;
+        let log_priors = class_sizes.iter().map(|&n| (n as f64 / doc_count).ln()).collect();
+        let mut base = vec![0.0; class_sizes.len()];
+        // the denominators of the term probabilities in each class.
+        let totals = match event {
+            EventModel::Multinomial => {
+                let vocab = term_counts.len() as f64;
+                let mut totals = vec![a * vocab; class_sizes.len()];
+                for counts in term_counts.values() {
+                    for (total, &count) in totals.iter_mut().zip(counts) {
+                        *total += count as f64
+                    }
+                }
+                totals
+            }
+            EventModel::Bernoulli => class_sizes.iter().map(|&n| n as f64 + 2.0 * a).collect(),
+        };
+        // sorted for summing `base` in the same order every time.
+        let mut terms = term_counts.keys().cloned().collect::<Vec<_>>();
+        terms.sort();
+        let log_probs = terms.into_iter()
+            .map(|term| {
+                let log_probs = term_counts[&term]
+                    .iter()
+                    .zip(&totals)
+                    .zip(&mut base)
+                    .map(|((&count, &total), base)| {
+                        let p = (count as f64 + a) / total;
+                        match event {
+                            EventModel::Multinomial => p.ln(),
+                            EventModel::Bernoulli => {
+                                *base += (1.0 - p).ln();
+                                p.ln() - (1.0 - p).ln()
+                            }
+                        }
+                    })
+                    .collect();
+                (term, log_probs)
+            })
+            .collect();
+        NaiveBayes {
+            event,
+            alpha,
+            class_sizes,
+            term_counts,
+            log_priors,
+            log_probs,
+            base,
+        }
+    }
+
+    pub fn event(&self) -> EventModel {
+        self.event
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// the number of training documents in each class.
+    pub fn class_sizes(&self) -> &[usize] {
+        &self.class_sizes
+    }
+
+    /// the number of terms known to the model.
+    pub fn vocab_size(&self) -> usize {
+        self.term_counts.len()
+    }
+
+    /// returns the joint log likelihood of a document with `(term, sign)`
+    /// pairs and each class. as in the inverted index, the signs of a term
+    /// are summed up into its frequency, and unknown terms are ignored.
+    pub fn log_likelihoods<I>(&self, terms: I) -> Vec<f64>
+        where I: IntoIterator<Item = (usize, isize)>
+    {
+        let mut term2tf = HashMap::new();
+        for (term, sign) in terms {
+            if self.log_probs.contains_key(&term) {
+                *term2tf.entry(term).or_insert(0) += sign
+            }
+        }
+        // sorted for summing in the same order every time.
+        let mut term2tf = term2tf.into_iter().filter(|&(_, tf)| tf != 0).collect::<Vec<_>>();
+        term2tf.sort();
+        let mut scores = self.log_priors
+            .iter()
+            .zip(&self.base)
+            .map(|(prior, base)| prior + base)
+            .collect::<Vec<f64>>();
+        for (term, tf) in term2tf {
+            let tf = match self.event {
+                EventModel::Multinomial => tf.unsigned_abs() as f64,
+                EventModel::Bernoulli => 1.0,
+            };
+            for (score, log_prob) in scores.iter_mut().zip(&self.log_probs[&term]) {
+                *score += tf * log_prob
+            }
+        }
+        scores
+    }
+
+    /// returns the most likely class, the first one on ties.
+    pub fn predict<I>(&self, terms: I) -> usize
+        where I: IntoIterator<Item = (usize, isize)>
+    {
+        argmax(&self.log_likelihoods(terms))
+    }
+
+    /// writes the counts in a text format, with the terms in ascending order.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "naive_bayes {}", self.event.name())?;
+        writeln!(wtr, "alpha {}", self.alpha)?;
+        write!(wtr, "class_sizes")?;
+        for size in &self.class_sizes {
+            write!(wtr, " {}", size)?;
+        }
+        writeln!(wtr)?;
+        writeln!(wtr, "terms {}", self.term_counts.len())?;
+        let mut terms = self.term_counts.keys().cloned().collect::<Vec<_>>();
+        terms.sort();
+        for term in terms {
+            write!(wtr, "{}", term)?;
+            for count in &self.term_counts[&term] {
+                write!(wtr, " {}", count)?;
+            }
+            writeln!(wtr)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R>(rdr: R) -> Result<NaiveBayes, LoadError>
+        where R: BufRead
+    {
+        let mut lines = rdr.lines();
+        let mut next_fields = || -> Result<(String, Vec<String>), LoadError> {
+            let line = match lines.next() {
+                Some(res_line) => res_line?,
+                None => return Err(FormatError::new("unexpected end").into()),
+            };
+            let mut fields = line.split_whitespace().map(str::to_owned).collect::<Vec<_>>();
+            if fields.is_empty() {
+                return Err(FormatError::new(&line).into());
+            }
+            let key = fields.remove(0);
+            Ok((key, fields))
+        };
+        let (key, fields) = next_fields()?;
+        let event = match (key.as_ref(), fields.first()) {
+            ("naive_bayes", Some(name)) => EventModel::from_name(name),
+            _ => None,
+        };
+        let event = event.ok_or_else(|| FormatError::new("missing naive_bayes"))?;
+        let (key, fields) = next_fields()?;
+        if key != "alpha" || fields.len() != 1 {
+            return Err(FormatError::new("missing alpha").into());
+        }
+        let alpha = fields[0].parse()?;
+        let (key, fields) = next_fields()?;
+        if key != "class_sizes" {
+            return Err(FormatError::new("missing class_sizes").into());
+        }
+        let class_sizes = fields.iter().map(|size| size.parse()).collect::<Result<Vec<_>, _>>()?;
+        let (key, fields) = next_fields()?;
+        if key != "terms" || fields.len() != 1 {
+            return Err(FormatError::new("missing terms").into());
+        }
+        let mut term_counts = HashMap::new();
+        for _ in 0..fields[0].parse()? {
+            let (term, counts) = next_fields()?;
+            if counts.len() != class_sizes.len() {
+                return Err(FormatError::new(&term).into());
+            }
+            let counts = counts.iter().map(|count| count.parse()).collect::<Result<_, _>>()?;
+            term_counts.insert(term.parse()?, counts);
+        }
+        Ok(NaiveBayes::from_counts(event, alpha, class_sizes, term_counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// class `0` has the documents `0 0 1` and `0`, class `1` the document
+    /// `1 2`.
+    fn train(event: EventModel) -> NaiveBayes {
+        let mut inv_idx = InvertedIndex::new();
+        for doc in &[vec![0, 0, 1], vec![0], vec![1, 2]] {
+            inv_idx.inv_push(doc.iter().cloned());
+        }
+        NaiveBayes::train(event, &inv_idx, &[0, 0, 1], 1.0, 1)
+    }
+
+    fn assert_close(scores: Vec<f64>, expected: &[f64]) {
+        for (score, &p) in scores.iter().zip(expected) {
+            assert!((score - p.ln()).abs() < 1e-9, "{} != ln {}", score, p);
+        }
+    }
+
+    #[test]
+    fn multinomial_probabilities() {
+        let nb = train(EventModel::Multinomial);
+        assert_eq!(nb.vocab_size(), 3);
+        // the counts plus one, over the class totals plus the vocabulary:
+        // `4/7, 2/7, 1/7` and `1/5, 2/5, 2/5`.
+        let terms = vec![(0, 1), (2, 1), (0, 1)];
+        assert_close(nb.log_likelihoods(terms.clone()),
+                     &[2.0 / 3.0 * 4.0 / 7.0 * 1.0 / 7.0 * 4.0 / 7.0,
+                       1.0 / 3.0 * 1.0 / 5.0 * 2.0 / 5.0 * 1.0 / 5.0]);
+        assert_eq!(nb.predict(terms), 0);
+        assert_eq!(nb.predict(vec![(2, 1), (1, 1)]), 1);
+    }
+
+    #[test]
+    fn bernoulli_probabilities() {
+        let nb = train(EventModel::Bernoulli);
+        // the document frequencies plus one, over the class sizes plus two:
+        // `3/4, 2/4, 1/4` and `1/3, 2/3, 2/3`, with absent terms counting as
+        // `1 - p`, and repeated or unknown terms as once or not at all.
+        let terms = vec![(0, 1), (2, 1), (0, 1), (7, 1)];
+        assert_close(nb.log_likelihoods(terms.clone()),
+                     &[2.0 / 3.0 * 3.0 / 4.0 * 2.0 / 4.0 * 1.0 / 4.0,
+                       1.0 / 3.0 * 1.0 / 3.0 * 1.0 / 3.0 * 2.0 / 3.0]);
+        assert_eq!(nb.predict(terms), 0);
+        assert_eq!(nb.predict(vec![(2, 1)]), 1);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        for &event in &[EventModel::Multinomial, EventModel::Bernoulli] {
+            let nb = train(event);
+            let mut buf = Vec::new();
+            nb.save(&mut buf).unwrap();
+            assert_eq!(NaiveBayes::load(&buf[..]).unwrap(), nb);
+        }
+    }
+}
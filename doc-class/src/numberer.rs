@@ -1,31 +1,226 @@
 // Author: Kuan Yu, 3913893
 // Honor Code:  I pledge that this program represents my own work.
 
+use error::{LoadError, FormatError};
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
 
 /// injective mapping: str -> usize
 pub trait Numberer {
+    /// returns the number of `s`, numbering it first when unseen; a frozen
+    /// numberer gives unseen strings its unk number, or `None` without one.
+    fn try_number(&mut self, s: &str) -> Option<usize>;
+
+    /// returns the number of `s`, numbering it first when unseen.
+    ///
+    /// # panics
+    ///
+    /// a numberer which can be frozen may panic for an unseen string when
+    /// frozen without an unk number; use `try_number` for those.
     fn number(&mut self, s: &str) -> usize;
+
+    /// returns the number of `s`, without numbering it.
+    fn get(&self, s: &str) -> Option<usize>;
+
+    /// returns the string numbered `n`.
+    fn name(&self, n: usize) -> Option<&str>;
+
+    /// the number of strings numbered so far.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
+/// numbers strings from `0` in the order of their first appearance.
+///
+/// # example
+/// ```
+/// use doc_class::numberer::{Numberer, HashMapNumberer};
+/// let mut numberer = HashMapNumberer::new();
+/// assert_eq!(numberer.number("a"), 0);
+/// assert_eq!(numberer.number("b"), 1);
+/// assert_eq!(numberer.number("a"), 0);
+/// assert_eq!(numberer.name(1), Some("b"));
+/// numberer.freeze();
+/// assert_eq!(numberer.try_number("c"), None);
+/// numberer.freeze_with_unk("<unk>");
+/// assert_eq!(numberer.try_number("c"), Some(2));
+/// assert_eq!(numberer.len(), 3);
+/// ```
 #[derive(Debug,Default,Clone,PartialEq,Eq)]
-pub struct HashMapNumberer(HashMap<String, usize>);
+pub struct HashMapNumberer {
+    numbers: HashMap<String, usize>,
+    strings: Vec<String>,
+    frozen: bool,
+    unk: Option<usize>,
+}
 
 impl HashMapNumberer {
     pub fn new() -> Self {
-        HashMapNumberer(HashMap::new())
+        HashMapNumberer::default()
+    }
+
+    /// numbers `strings` in order; repeated strings keep their first number.
+    pub fn from_strings<I, S>(strings: I) -> Self
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>
+    {
+        let mut numberer = HashMapNumberer::new();
+        for s in strings {
+            numberer.number(s.as_ref());
+        }
+        numberer
+    }
+
+    /// stops numbering unseen strings.
+    pub fn freeze(&mut self) {
+        self.frozen = true
+    }
+
+    /// numbers `unk` unless seen, and stops numbering unseen strings, which
+    /// get the number of `unk` instead.
+    pub fn freeze_with_unk(&mut self, unk: &str) {
+        self.frozen = false;
+        self.unk = Some(self.number(unk));
+        self.frozen = true
+    }
+
+    /// numbers unseen strings again, and forgets the unk number.
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+        self.unk = None
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// the number for unseen strings when frozen, if any.
+    pub fn unk(&self) -> Option<usize> {
+        self.unk
+    }
+
+    /// the strings by their numbers.
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    /// returns `(number, string)` pairs in the order of the numbers.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+        self.strings.iter().map(String::as_str).enumerate()
+    }
+
+    /// writes a line `NUMBER\tSTRING` for each string, in the order of the
+    /// numbers. the label dictionaries and the classes of a saved
+    /// `Vectorizer` are kept this way.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        for (n, s) in self.iter() {
+            writeln!(wtr, "{}\t{}", n, s)?;
+        }
+        Ok(())
+    }
+
+    /// reads what `save` wrote into an unfrozen numberer.
+    pub fn load<R>(rdr: R) -> Result<HashMapNumberer, LoadError>
+        where R: BufRead
+    {
+        let mut numberer = HashMapNumberer::new();
+        for res_line in rdr.lines() {
+            numberer.load_line(&res_line?)?;
+        }
+        Ok(numberer)
+    }
+
+    /// numbers the string in a line written by `save`, which must be the
+    /// next number, for reading the lines as part of another file.
+    pub fn load_line(&mut self, line: &str) -> Result<(), LoadError> {
+        let x = line.find('\t').ok_or_else(|| FormatError::new(line))?;
+        let s = &line[x + 1..];
+        if line[..x].parse::<usize>()? != self.len() || self.numbers.contains_key(s) {
+            return Err(FormatError::new(line).into());
+        }
+        self.number(s);
+        Ok(())
     }
 }
 
 impl Numberer for HashMapNumberer {
+    /// # panics
+    ///
+    /// for an unseen string when frozen without an unk number.
     fn number(&mut self, s: &str) -> usize {
-        match self.0.get(s) {
-            Some(&n) => n,
+        match self.try_number(s) {
+            Some(n) => n,
+            None => panic!("unseen string in a frozen numberer: {}", s),
+        }
+    }
+
+    fn try_number(&mut self, s: &str) -> Option<usize> {
+        match self.numbers.get(s) {
+            Some(&n) => Some(n),
+            None if self.frozen => self.unk,
             None => {
-                let n = self.0.len();
-                self.0.insert(s.to_owned(), n);
-                n
+                let n = self.strings.len();
+                self.numbers.insert(s.to_owned(), n);
+                self.strings.push(s.to_owned());
+                Some(n)
             }
         }
     }
+
+    fn get(&self, s: &str) -> Option<usize> {
+        self.numbers.get(s).cloned()
+    }
+
+    fn name(&self, n: usize) -> Option<&str> {
+        self.strings.get(n).map(String::as_str)
+    }
+
+    fn len(&self) -> usize {
+        self.strings.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let numberer = HashMapNumberer::from_strings(["spam", "ham", "a b\tc"]);
+        let mut buf = Vec::new();
+        numberer.save(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf.clone()).unwrap(), "0\tspam\n1\tham\n2\ta b\tc\n");
+        assert_eq!(HashMapNumberer::load(&buf[..]).unwrap(), numberer);
+    }
+
+    #[test]
+    fn load_rejects_misnumbered_lines() {
+        assert!(HashMapNumberer::load("1\tspam\n".as_bytes()).is_err());
+        assert!(HashMapNumberer::load("0\tspam\n1\tspam\n".as_bytes()).is_err());
+        assert!(HashMapNumberer::load("spam\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn frozen_numberer_keeps_its_numbers() {
+        let mut numberer = HashMapNumberer::from_strings(["a"]);
+        numberer.freeze();
+        assert_eq!(numberer.try_number("b"), None);
+        assert_eq!(numberer.number("a"), 0);
+        assert_eq!(numberer.len(), 1);
+        numberer.unfreeze();
+        assert_eq!(numberer.number("b"), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "unseen string")]
+    fn number_panics_when_frozen_without_unk() {
+        let mut numberer = HashMapNumberer::new();
+        numberer.freeze();
+        numberer.number("a");
+    }
 }
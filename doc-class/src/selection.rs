@@ -0,0 +1,207 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use inverted_index::{InvertedIndex, class_sizes};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// supervised scores for terms, computed from the document frequencies of each
+/// term in and out of each class.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Criterion {
+    /// the chi-square statistic of term and class.
+    Chi2,
+    /// the information gain, i.e. the mutual information between the presence
+    /// of a term and the class.
+    InfoGain,
+    /// the pointwise mutual information of term and class.
+    Pmi,
+    /// the ratio of the relative document frequencies in and out of the
+    /// class, with add-one smoothing.
+    DfRatio,
+}
+
+impl Criterion {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Criterion::Chi2 => "chi2",
+            Criterion::InfoGain => "ig",
+            Criterion::Pmi => "pmi",
+            Criterion::DfRatio => "dfr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Criterion> {
+        match name {
+            "chi2" => Some(Criterion::Chi2),
+            "ig" => Some(Criterion::InfoGain),
+            "pmi" => Some(Criterion::Pmi),
+            "dfr" => Some(Criterion::DfRatio),
+            _ => None,
+        }
+    }
+
+    /// scores a term against one class, from the 2x2 contingency table:
+    /// `a` documents in the class have the term, `b` outside the class have
+    /// it, and `n_c` out of all `n` documents are in the class.
+    pub fn class_score(&self, a: usize, b: usize, n_c: usize, n: usize) -> f32 {
+        let (a, b, n_c, n) = (a as f64, b as f64, n_c as f64, n as f64);
+        let c = n_c - a;
+        let d = n - n_c - b;
+        let score = match *self {
+            Criterion::Chi2 => {
+                let denom = (a + c) * (b + d) * (a + b) * (c + d);
+                if denom == 0.0 {
+                    0.0
+                } else {
+                    n * (a * d - b * c).powi(2) / denom
+                }
+            }
+            Criterion::InfoGain => {
+                mi_part(a, n, a + b, a + c) + mi_part(b, n, a + b, b + d) +
+                mi_part(c, n, c + d, a + c) + mi_part(d, n, c + d, b + d)
+            }
+            Criterion::Pmi => {
+                if a == 0.0 {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::ln(a * n / ((a + b) * n_c))
+                }
+            }
+            Criterion::DfRatio => ((a + 1.0) / (n_c + 1.0)) / ((b + 1.0) / (n - n_c + 1.0)),
+        };
+        score as f32
+    }
+}
+
+/// `p(x, y) log (p(x, y) / (p(x) p(y)))` from the counts `n_xy`, `n_x`, and
+/// `n_y` out of `n`.
+fn mi_part(n_xy: f64, n: f64, n_x: f64, n_y: f64) -> f64 {
+    if n_xy == 0.0 {
+        0.0
+    } else {
+        n_xy / n * f64::ln(n_xy * n / (n_x * n_y))
+    }
+}
+
+/// returns the terms in `inv_idx` to keep, out of those with at least
+/// `min_freq`: either the `k` best by their maximal score over all classes,
+/// or the `k` best for each class when `per_class`. the information gain is
+/// computed for all classes at once instead of maximized over the classes.
+/// `labels` are the classes of the documents, which must be numbered from `0`.
+pub fn select_terms(inv_idx: &InvertedIndex,
+                    labels: &[usize],
+                    criterion: Criterion,
+                    k: usize,
+                    min_freq: usize,
+                    per_class: bool)
+                    -> HashSet<usize> {
+    let n = labels.len();
+    let class_sizes = class_sizes(labels);
+    let class_count = class_sizes.len();
+
+    // one row of scores for each class, aligned with `terms`.
+    let mut terms = Vec::new();
+    let mut scores = vec![Vec::new(); class_count];
+    let mut global = Vec::new();
+    let mut class_dfs = vec![0; class_count];
+    for (term, df) in inv_idx.doc_freqs(min_freq) {
+        class_dfs.fill(0);
+        for &(doc, _) in &inv_idx.view_content()[&term] {
+            class_dfs[labels[doc]] += 1
+        }
+        terms.push(term);
+        let mut best = f32::NEG_INFINITY;
+        for c in 0..class_count {
+            let score = criterion.class_score(class_dfs[c], df - class_dfs[c], class_sizes[c], n);
+            scores[c].push(score);
+            best = best.max(score);
+        }
+        if criterion == Criterion::InfoGain {
+            // the mutual information with the class variable as a whole,
+            // instead of the best one among the binary splits.
+            best = (0..class_count)
+                .map(|c| {
+                    let (a, n_c) = (class_dfs[c] as f64, class_sizes[c] as f64);
+                    mi_part(a, n as f64, df as f64, n_c) +
+                    mi_part(n_c - a, n as f64, (n - df) as f64, n_c)
+                })
+                .sum::<f64>() as f32
+        }
+        global.push(best);
+    }
+
+    let mut keep = HashSet::new();
+    if per_class {
+        for class_scores in &scores {
+            keep.extend(top_k(class_scores, k).into_iter().map(|i| terms[i]))
+        }
+    } else {
+        keep.extend(top_k(&global, k).into_iter().map(|i| terms[i]))
+    }
+    keep
+}
+
+/// returns the positions of the `k` largest scores.
+fn top_k(scores: &[f32], k: usize) -> Vec<usize> {
+    let mut idxs = (0..scores.len()).collect::<Vec<_>>();
+    idxs.sort_by(|&i, &j| scores[j].partial_cmp(&scores[i]).unwrap_or(Ordering::Equal));
+    idxs.truncate(k);
+    idxs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 40 of the 50 documents in the class have the term, and 10 of the 50
+    // outside of it.
+    fn score(criterion: Criterion) -> f32 {
+        criterion.class_score(40, 10, 50, 100)
+    }
+
+    #[test]
+    fn chi2_on_a_contingency_table() {
+        // 100 (40 * 40 - 10 * 10)^2 / 50^4
+        assert!((score(Criterion::Chi2) - 36.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn info_gain_on_a_contingency_table() {
+        // 2 * 0.4 ln(0.4 / 0.25) + 2 * 0.1 ln(0.1 / 0.25)
+        let expected = 0.8 * f32::ln(1.6) + 0.2 * f32::ln(0.4);
+        assert!((score(Criterion::InfoGain) - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pmi_on_a_contingency_table() {
+        assert!((score(Criterion::Pmi) - f32::ln(1.6)).abs() < 1e-6);
+        assert_eq!(Criterion::Pmi.class_score(0, 10, 50, 100), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn df_ratio_on_a_contingency_table() {
+        assert!((score(Criterion::DfRatio) - 41.0 / 11.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn independent_term_scores_nothing() {
+        for &criterion in &[Criterion::Chi2, Criterion::InfoGain, Criterion::Pmi] {
+            assert!(criterion.class_score(25, 25, 50, 100).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn select_terms_keeps_the_class_specific_ones() {
+        // term `1` occurs everywhere, `0` only in class `0`, `2` only in `1`.
+        let docs = [vec![0, 1], vec![0, 1], vec![1, 2], vec![1, 2]];
+        let labels = [0, 0, 1, 1];
+        let mut inv_idx = InvertedIndex::new();
+        for doc in &docs {
+            inv_idx.inv_push(doc.iter().cloned());
+        }
+        let expected = [0, 2].iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(select_terms(&inv_idx, &labels, Criterion::Chi2, 2, 1, false), expected);
+        assert_eq!(select_terms(&inv_idx, &labels, Criterion::Pmi, 1, 1, true), expected);
+    }
+}
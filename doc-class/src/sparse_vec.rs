@@ -3,14 +3,251 @@
 
 use protocoll::map::VecSortedMap;
 use protocoll::MapMut;
+use std::cmp::Ordering;
 
 pub type SparseVec<T> = VecSortedMap<usize, T>;
 
+#[allow(clippy::assign_op_pattern)]
 pub fn scale_to_unit(v: &mut SparseVec<f32>) {
+    let norm = l2_norm(v);
+    if norm > 0.0 {
+        v.update_all_mut(|_, x| *x = *x / norm);
+    }
+}
+
+/// scales `v` so that its absolute values sum up to `1`.
+pub fn scale_by_l1(v: &mut SparseVec<f32>) {
+    let norm = l1_norm(v);
+    if norm > 0.0 {
+        v.update_all_mut(|_, x| *x /= norm);
+    }
+}
+
+/// multiplies every entry of `v` by `a`.
+pub fn scale(v: &mut SparseVec<f32>, a: f32) {
+    v.update_all_mut(|_, x| *x *= a);
+}
+
+/// the sum of the absolute values.
+pub fn l1_norm(v: &SparseVec<f32>) -> f32 {
+    v.iter().map(|&(_, x)| x.abs()).sum()
+}
+
+/// the euclidean length.
+pub fn l2_norm(v: &SparseVec<f32>) -> f32 {
     let mut xx = 0.0;
     for &(_, x) in v.iter() {
         xx += x * x
     }
-    let norm = f32::sqrt(xx);
-    v.update_all_mut(|_, x| *x = *x / norm);
+    f32::sqrt(xx)
+}
+
+/// the largest absolute value, or `0` for none.
+pub fn max_norm(v: &SparseVec<f32>) -> f32 {
+    v.iter().fold(0.0, |max, &(_, x)| max.max(x.abs()))
+}
+
+pub fn dot(x: &SparseVec<f32>, y: &SparseVec<f32>) -> f32 {
+    let (x, y) = (x.view_content(), y.view_content());
+    let (mut i, mut j) = (0, 0);
+    let mut dot = 0.0;
+    while i < x.len() && j < y.len() {
+        match x[i].0.cmp(&y[j].0) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                dot += x[i].1 * y[j].1;
+                i += 1;
+                j += 1
+            }
+        }
+    }
+    dot
+}
+
+/// returns the dot product of `x` and the dense `w`, ignoring dimensions
+/// beyond `w`.
+pub fn dot_dense(x: &SparseVec<f32>, w: &[f32]) -> f32 {
+    let mut dot = 0.0;
+    for &(i, v) in x {
+        if let Some(&u) = w.get(i) {
+            dot += u * v
+        }
+    }
+    dot
+}
+
+/// the cosine of the angle between `x` and `y`, or `0` if either is zero.
+pub fn cosine(x: &SparseVec<f32>, y: &SparseVec<f32>) -> f32 {
+    let norms = l2_norm(x) * l2_norm(y);
+    if norms > 0.0 { dot(x, y) / norms } else { 0.0 }
+}
+
+/// combines `x` and `y` dimension by dimension with `f`, which gets `0` for
+/// a missing entry. entries which come out as zero are dropped.
+fn combine<F>(x: &SparseVec<f32>, y: &SparseVec<f32>, f: F) -> SparseVec<f32>
+    where F: Fn(f32, f32) -> f32
+{
+    let (x, y) = (x.view_content(), y.view_content());
+    let mut z = SparseVec::with_capacity(x.len().max(y.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < x.len() || j < y.len() {
+        let (dim, value) = match (x.get(i), y.get(j)) {
+            (Some(&(xi, xv)), Some(&(yi, _))) if xi < yi => {
+                i += 1;
+                (xi, f(xv, 0.0))
+            }
+            (Some(&(xi, xv)), Some(&(yi, yv))) if xi == yi => {
+                i += 1;
+                j += 1;
+                (xi, f(xv, yv))
+            }
+            (_, Some(&(yi, yv))) => {
+                j += 1;
+                (yi, f(0.0, yv))
+            }
+            (Some(&(xi, xv)), None) => {
+                i += 1;
+                (xi, f(xv, 0.0))
+            }
+            (None, None) => unreachable!(),
+        };
+        if value != 0.0 {
+            z.insert(dim, value);
+        }
+    }
+    z
+}
+
+/// adds `a * x` to `y`, dropping the entries which cancel out.
+pub fn axpy(a: f32, x: &SparseVec<f32>, y: &mut SparseVec<f32>) {
+    *y = combine(x, y, |u, v| a * u + v)
+}
+
+/// returns `x + y`, without the entries which cancel out.
+pub fn add(x: &SparseVec<f32>, y: &SparseVec<f32>) -> SparseVec<f32> {
+    combine(x, y, |u, v| u + v)
+}
+
+/// returns `x - y`, without the entries which cancel out.
+pub fn sub(x: &SparseVec<f32>, y: &SparseVec<f32>) -> SparseVec<f32> {
+    combine(x, y, |u, v| u - v)
+}
+
+/// returns the `k` entries with the largest values, in descending order of
+/// value, and in ascending order of dimension on ties.
+pub fn top_k(v: &SparseVec<f32>, k: usize) -> Vec<(usize, f32)> {
+    let mut entries = v.view_content().clone();
+    entries.sort_by(|&(i, x), &(j, y)| y.partial_cmp(&x).unwrap_or(Ordering::Equal).then(i.cmp(&j)));
+    entries.truncate(k);
+    entries
+}
+
+/// returns the index of the largest score, the first one on ties, or `0` for
+/// none.
+pub fn argmax<T>(scores: &[T]) -> usize
+    where T: PartialOrd
+{
+    let mut best = 0;
+    for (k, score) in scores.iter().enumerate() {
+        if scores[best] < *score {
+            best = k
+        }
+    }
+    best
+}
+
+/// returns `v` as a dense vector of `dim` values, ignoring dimensions beyond.
+pub fn to_dense(v: &SparseVec<f32>, dim: usize) -> Vec<f32> {
+    let mut dense = vec![0.0; dim];
+    for &(i, x) in v {
+        if i < dim {
+            dense[i] = x
+        }
+    }
+    dense
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sv(entries: &[(usize, f32)]) -> SparseVec<f32> {
+        entries.iter().cloned().collect()
+    }
+
+    #[test]
+    fn dot_products_over_supports() {
+        let x = sv(&[(0, 1.0), (2, 2.0), (5, -1.0)]);
+        assert_eq!(dot(&x, &sv(&[(1, 3.0), (3, 4.0)])), 0.0);
+        assert_eq!(dot(&x, &sv(&[(2, 3.0), (4, 7.0), (5, 2.0)])), 4.0);
+        assert_eq!(dot(&x, &x), 6.0);
+        assert_eq!(dot(&x, &SparseVec::new()), 0.0);
+        assert_eq!(dot_dense(&x, &[2.0, 9.0, 0.5]), 3.0);
+        assert_eq!(dot_dense(&x, &[]), 0.0);
+    }
+
+    #[test]
+    fn cosine_of_zero_vectors_is_zero() {
+        let x = sv(&[(0, 3.0), (1, 4.0)]);
+        assert!((cosine(&x, &sv(&[(0, 6.0), (1, 8.0)])) - 1.0).abs() < 1e-6);
+        assert!((cosine(&x, &sv(&[(0, -3.0), (1, -4.0)])) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine(&x, &sv(&[(2, 1.0)])), 0.0);
+        assert_eq!(cosine(&x, &SparseVec::new()), 0.0);
+        assert_eq!(cosine(&SparseVec::new(), &SparseVec::new()), 0.0);
+        assert_eq!(cosine(&x, &sv(&[(0, 0.0)])), 0.0);
+    }
+
+    #[test]
+    fn add_sub_and_axpy_drop_cancelled_entries() {
+        let x = sv(&[(0, 1.0), (2, 2.0), (5, -1.0)]);
+        let y = sv(&[(1, 3.0), (2, -2.0), (5, 1.0), (7, 1.0)]);
+        assert_eq!(add(&x, &y), sv(&[(0, 1.0), (1, 3.0), (7, 1.0)]));
+        assert_eq!(sub(&x, &y), sv(&[(0, 1.0), (1, -3.0), (2, 4.0), (5, -2.0), (7, -1.0)]));
+        assert_eq!(sub(&x, &x), SparseVec::new());
+        assert_eq!(add(&x, &SparseVec::new()), x);
+        let mut z = y.clone();
+        axpy(2.0, &x, &mut z);
+        assert_eq!(z, sv(&[(0, 2.0), (1, 3.0), (2, 2.0), (5, -1.0), (7, 1.0)]));
+        let mut z = x.clone();
+        axpy(-1.0, &x, &mut z);
+        assert!(z.is_empty());
+    }
+
+    #[test]
+    fn top_k_sorts_by_value_then_dimension() {
+        let v = sv(&[(0, 1.0), (1, 3.0), (2, -2.0), (3, 3.0)]);
+        assert_eq!(top_k(&v, 2), [(1, 3.0), (3, 3.0)]);
+        assert_eq!(top_k(&v, 10), [(1, 3.0), (3, 3.0), (0, 1.0), (2, -2.0)]);
+        assert!(top_k(&v, 0).is_empty());
+        assert!(top_k(&SparseVec::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn argmax_takes_the_first_of_ties() {
+        assert_eq!(argmax(&[1.0, 3.0, -2.0, 3.0]), 1);
+        assert_eq!(argmax(&[-1.0f64, -0.5]), 1);
+        assert_eq!(argmax::<f32>(&[]), 0);
+    }
+
+    #[test]
+    fn norms_and_scaling() {
+        let v = sv(&[(1, 3.0), (4, -4.0)]);
+        assert_eq!(l1_norm(&v), 7.0);
+        assert_eq!(l2_norm(&v), 5.0);
+        assert_eq!(max_norm(&v), 4.0);
+        assert_eq!(max_norm(&SparseVec::new()), 0.0);
+        let mut u = v.clone();
+        scale_by_l1(&mut u);
+        assert_eq!(u, sv(&[(1, 3.0 / 7.0), (4, -4.0 / 7.0)]));
+        let mut u = v.clone();
+        scale_to_unit(&mut u);
+        assert_eq!(u, sv(&[(1, 0.6), (4, -0.8)]));
+        let mut zero = SparseVec::new();
+        scale_by_l1(&mut zero);
+        scale_to_unit(&mut zero);
+        assert!(zero.is_empty());
+        assert_eq!(to_dense(&v, 3), [0.0, 3.0, 0.0]);
+        assert_eq!(to_dense(&v, 5), [0.0, 3.0, 0.0, 0.0, -4.0]);
+    }
 }
@@ -0,0 +1,309 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::{SparseVec, dot_dense, argmax};
+use inverted_index::class_sizes;
+use formats::{write_svm, read_svm};
+use error::{LoadError, FormatError};
+use std::cmp::Ordering;
+use std::io::{self, BufRead, Write};
+
+/// reads a line `key value` from `rdr`.
+fn read_header<R>(rdr: &mut R, key: &str) -> Result<String, LoadError>
+    where R: BufRead
+{
+    let mut line = String::new();
+    rdr.read_line(&mut line)?;
+    let mut fields = line.split_whitespace();
+    match (fields.next(), fields.next()) {
+        (Some(k), Some(value)) if k == key => Ok(value.to_owned()),
+        _ => Err(FormatError::new(&format!("missing {}", key)).into()),
+    }
+}
+
+/// the rocchio classifier: each class is represented by the centroid of its
+/// feature vectors, scaled to unit length, and a document goes to the class
+/// whose centroid is the most similar by cosine. since the feature vectors
+/// are unit vectors, the cosine is just the dot product.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NearestCentroid {
+    /// the dense centroid of each class, numbered from `0`.
+    centroids: Vec<Vec<f32>>,
+}
+
+impl NearestCentroid {
+    /// `labels` are the classes of `features`, numbered from `0`.
+    pub fn train(features: &[SparseVec<f32>], labels: &[usize]) -> Self {
+        let dim = features.iter()
+            .filter_map(|x| x.view_content().last().map(|&(i, _)| i + 1))
+            .max()
+            .unwrap_or(0);
+        let class_count = labels.iter().max().map_or(0, |&l| l + 1);
+        let mut centroids = vec![vec![0.0; dim]; class_count];
+        for (x, &label) in features.iter().zip(labels) {
+            for &(i, v) in x {
+                centroids[label][i] += v
+            }
+        }
+        for centroid in &mut centroids {
+            let norm = centroid.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if 0.0 < norm {
+                for v in centroid.iter_mut() {
+                    *v /= norm
+                }
+            }
+        }
+        NearestCentroid { centroids }
+    }
+
+    pub fn centroids(&self) -> &[Vec<f32>] {
+        &self.centroids
+    }
+
+    /// returns the cosine similarity of `x` with each centroid, assuming `x`
+    /// has unit length.
+    pub fn similarities(&self, x: &SparseVec<f32>) -> Vec<f32> {
+        self.centroids.iter().map(|centroid| dot_dense(x, centroid)).collect()
+    }
+
+    pub fn predict(&self, x: &SparseVec<f32>) -> usize {
+        argmax(&self.similarities(x))
+    }
+
+    /// writes the line `nearest_centroid DIM`, then the centroids in svmlight
+    /// format, labelled with their classes.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "nearest_centroid {}", self.centroids.first().map_or(0, Vec::len))?;
+        let labels = (0..self.centroids.len()).collect::<Vec<_>>();
+        let centroids = self.centroids
+            .iter()
+            .map(|centroid| {
+                centroid.iter().cloned().enumerate().filter(|&(_, v)| v != 0.0).collect()
+            })
+            .collect::<Vec<_>>();
+        write_svm(wtr, &labels, &centroids)
+    }
+
+    pub fn load<R>(mut rdr: R) -> Result<NearestCentroid, LoadError>
+        where R: BufRead
+    {
+        let dim = read_header(&mut rdr, "nearest_centroid")?.parse()?;
+        let (labels, sparse_centroids) = read_svm(rdr)?;
+        let mut centroids = Vec::with_capacity(sparse_centroids.len());
+        for (label, sparse_centroid) in labels.into_iter().zip(sparse_centroids) {
+            if label != centroids.len() {
+                return Err(FormatError::new(&format!("centroid of class {}", label)).into());
+            }
+            let mut centroid = vec![0.0; dim];
+            for (i, v) in sparse_centroid {
+                *centroid.get_mut(i).ok_or_else(|| FormatError::new("dimension beyond dim"))? = v
+            }
+            centroids.push(centroid);
+        }
+        Ok(NearestCentroid { centroids })
+    }
+}
+
+/// the k-nearest-neighbour classifier by cosine similarity, where each of the
+/// `k` most similar training documents votes for its class with its
+/// similarity. the candidates are found through an inverted index from the
+/// dimensions to the training documents, so that only documents sharing a
+/// dimension with the query are scored.
+#[derive(Debug,Clone,PartialEq)]
+pub struct KNearest {
+    k: usize,
+    labels: Vec<usize>,
+    features: Vec<SparseVec<f32>>,
+    /// for each dimension, the `(doc, value)` pairs of the training documents
+    /// in ascending order.
+    postings: Vec<Vec<(usize, f32)>>,
+    /// the class with the most training documents, for queries without any
+    /// similar document.
+    majority: usize,
+}
+
+impl KNearest {
+    /// keeps `features` with their `labels`, numbered from `0`.
+    pub fn train(k: usize, features: Vec<SparseVec<f32>>, labels: Vec<usize>) -> Self {
+        let dim = features.iter()
+            .filter_map(|x| x.view_content().last().map(|&(i, _)| i + 1))
+            .max()
+            .unwrap_or(0);
+        let mut postings = vec![Vec::new(); dim];
+        for (doc, x) in features.iter().enumerate() {
+            for &(i, v) in x {
+                postings[i].push((doc, v))
+            }
+        }
+        let majority = argmax(&class_sizes(&labels));
+        KNearest {
+            k,
+            labels,
+            features,
+            postings,
+            majority,
+        }
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// returns up to `k` pairs of `(doc, similarity)` for the training
+    /// documents most similar to `x`, in descending similarity and ascending
+    /// position on ties; only documents with positive similarity count.
+    pub fn neighbors(&self, x: &SparseVec<f32>) -> Vec<(usize, f32)> {
+        // the scores are accumulated densely, with the candidates listed as
+        // they are first reached.
+        let mut scores = vec![0.0; self.labels.len()];
+        let mut reached = vec![false; self.labels.len()];
+        let mut candidates = Vec::new();
+        for &(i, v) in x {
+            if let Some(postings) = self.postings.get(i) {
+                for &(doc, u) in postings {
+                    if !reached[doc] {
+                        reached[doc] = true;
+                        candidates.push(doc)
+                    }
+                    scores[doc] += u * v
+                }
+            }
+        }
+        let mut neighbors = candidates.into_iter()
+            .map(|doc| (doc, scores[doc]))
+            .filter(|&(_, score)| 0.0 < score)
+            .collect::<Vec<_>>();
+        let by_score = |&(doc1, score1): &(usize, f32), &(doc2, score2): &(usize, f32)| {
+            score2.partial_cmp(&score1).unwrap_or(Ordering::Equal).then(doc1.cmp(&doc2))
+        };
+        if self.k < neighbors.len() {
+            neighbors.select_nth_unstable_by(self.k, by_score);
+            neighbors.truncate(self.k);
+        }
+        neighbors.sort_by(by_score);
+        neighbors
+    }
+
+    pub fn predict(&self, x: &SparseVec<f32>) -> usize {
+        let neighbors = self.neighbors(x);
+        if neighbors.is_empty() {
+            return self.majority;
+        }
+        let mut votes = vec![0.0; self.labels.iter().max().map_or(0, |&l| l + 1)];
+        for (doc, score) in neighbors {
+            votes[self.labels[doc]] += score
+        }
+        argmax(&votes)
+    }
+
+    /// writes the line `knn K`, then the training documents in svmlight
+    /// format.
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        writeln!(wtr, "knn {}", self.k)?;
+        write_svm(wtr, &self.labels, &self.features)
+    }
+
+    pub fn load<R>(mut rdr: R) -> Result<KNearest, LoadError>
+        where R: BufRead
+    {
+        let k = read_header(&mut rdr, "knn")?.parse()?;
+        let (labels, features) = read_svm(rdr)?;
+        Ok(KNearest::train(k, features, labels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec(entries: &[(usize, f32)]) -> SparseVec<f32> {
+        entries.iter().cloned().collect()
+    }
+
+    /// two classes around the first two dimensions, and a third class with
+    /// a single document.
+    fn toy_set() -> (Vec<SparseVec<f32>>, Vec<usize>) {
+        let features = vec![vec(&[(0, 0.8), (1, 0.6)]),
+                            vec(&[(0, 1.0)]),
+                            vec(&[(1, 1.0)]),
+                            vec(&[(0, 0.6), (1, 0.8)]),
+                            vec(&[(1, 0.6), (2, 0.8)])];
+        (features, vec![0, 0, 1, 1, 2])
+    }
+
+    #[test]
+    fn nearest_centroid_predicts_the_closest_class() {
+        let (features, labels) = toy_set();
+        let model = NearestCentroid::train(&features, &labels);
+        assert_eq!(model.centroids().len(), 3);
+        let norm = f32::sqrt(1.8 * 1.8 + 0.6 * 0.6);
+        assert_eq!(model.centroids()[0], [1.8 / norm, 0.6 / norm, 0.0]);
+        assert_eq!(model.centroids()[2], [0.0, 0.6, 0.8]);
+        for (x, &label) in features.iter().zip(&labels) {
+            assert_eq!(model.predict(x), label);
+        }
+        assert_eq!(model.predict(&vec(&[(2, 1.0)])), 2);
+        // no similarity with any class goes to the first one.
+        assert_eq!(model.predict(&vec(&[(5, 1.0)])), 0);
+    }
+
+    #[test]
+    fn nearest_centroid_save_and_load_round_trip() {
+        let (features, labels) = toy_set();
+        let model = NearestCentroid::train(&features, &labels);
+        let mut buf = Vec::new();
+        model.save(&mut buf).unwrap();
+        assert_eq!(NearestCentroid::load(&buf[..]).unwrap(), model);
+        assert!(NearestCentroid::load("knn 3\n".as_bytes()).is_err());
+        assert!(NearestCentroid::load("nearest_centroid 1\n0 3:1\n".as_bytes()).is_err());
+        assert!(NearestCentroid::load("nearest_centroid 1\n1 0:1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn knn_falls_back_to_the_majority_class() {
+        let (features, labels) = toy_set();
+        let knn = KNearest::train(1, features.clone(), vec![1, 0, 2, 2, 1]);
+        assert_eq!(knn.predict(&vec(&[(9, 1.0)])), 1);
+        let knn = KNearest::train(2, features, labels);
+        assert_eq!(knn.predict(&vec(&[(9, 1.0)])), 0);
+        assert_eq!(knn.predict(&SparseVec::new()), 0);
+    }
+
+    #[test]
+    fn knn_save_and_load_round_trip() {
+        let (features, labels) = toy_set();
+        let knn = KNearest::train(2, features.clone(), labels);
+        let mut buf = Vec::new();
+        knn.save(&mut buf).unwrap();
+        let loaded = KNearest::load(&buf[..]).unwrap();
+        assert_eq!(loaded, knn);
+        assert_eq!(loaded.k(), 2);
+        for x in &features {
+            assert_eq!(loaded.neighbors(x), knn.neighbors(x));
+        }
+        assert!(KNearest::load("nearest_centroid 3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn neighbors_by_descending_similarity() {
+        let features = vec![vec(&[(0, 1.0)]),
+                            vec(&[(0, 0.5), (1, 0.5)]),
+                            vec(&[(1, 1.0)]),
+                            vec(&[(0, 1.0)]),
+                            vec(&[(2, 1.0)]),
+                            vec(&[(0, -1.0)])];
+        let knn = KNearest::train(3, features, vec![0, 0, 1, 1, 2, 2]);
+        // ties go to the earlier document, and documents without positive
+        // similarity are never neighbors.
+        assert_eq!(knn.neighbors(&vec(&[(0, 1.0), (1, 0.25)])),
+                   [(0, 1.0), (3, 1.0), (1, 0.625)]);
+        assert_eq!(knn.neighbors(&vec(&[(1, 1.0)])), [(2, 1.0), (1, 0.5)]);
+        assert!(knn.neighbors(&vec(&[(7, 1.0)])).is_empty());
+        assert_eq!(knn.predict(&vec(&[(2, 1.0)])), 2);
+        assert_eq!(knn.predict(&vec(&[(1, 1.0)])), 1);
+    }
+}
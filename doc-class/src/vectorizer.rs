@@ -0,0 +1,754 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::SparseVec;
+use inverted_index::{InvertedIndex, TermStats, mean, class_sizes};
+use weighting::{Weighting, Feature, Norm, TermScope};
+use hashing::{FeatureHasher, HashAlgorithm};
+use extraction::{NgramExtractor, NgramSpec, TokenUnit, Token, DepToken, parse_specs, format_specs,
+                 read_clusters};
+use filters::{StopwordFilter, Stopwords, ListStopwordFilter};
+use embedding::{DocEmbedder, Pooling, read_embeddings};
+use numberer::{Numberer, HashMapNumberer};
+use dictionary::TermNames;
+use error::{LoadError, FormatError};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::{self, BufRead, Write};
+use protocoll::MapMut;
+
+/// everything needed for featurizing new documents into the same space as
+/// the documents it was fitted on.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Vectorizer {
+    stopwords: Stopwords,
+    extractor: NgramExtractor,
+    feature: Feature,
+    norm: Norm,
+    doc_count: usize,
+    avg_doc_len: f32,
+    classes: Vec<String>,
+    class_sizes: Vec<usize>,
+    terms: Vec<usize>,
+    doc_freqs: Vec<usize>,
+    /// the document frequencies in each class for each term, only kept when
+    /// the feature `needs_classes`.
+    class_dfs: Vec<Vec<usize>>,
+    term2dim: HashMap<usize, usize>,
+    embedder: Option<DocEmbedder>,
+}
+
+impl Vectorizer {
+    /// `stopwords` and `extractor` are what produced the terms in `inv_idx`;
+    /// `labels` are the classes of its documents, numbered by their positions
+    /// in `classes`. terms in `inv_idx` under `min_freq` are ignored. the
+    /// features are scaled by the `L2` norm, unless set by `with_norm`.
+    pub fn fit(inv_idx: &InvertedIndex,
+               labels: &[usize],
+               stopwords: Stopwords,
+               extractor: NgramExtractor,
+               feature: Feature,
+               classes: Vec<String>,
+               min_freq: usize)
+               -> Self {
+        let stats = inv_idx.term_stats(labels, classes.len(), min_freq, feature.needs_classes());
+        Vectorizer::from_stats(stats, labels, stopwords, extractor, feature, classes)
+    }
+
+    /// as `fit`, but from the statistics of an index, which must have the
+    /// document frequencies in each class when the feature `needs_classes`.
+    pub fn from_stats(stats: TermStats,
+                      labels: &[usize],
+                      stopwords: Stopwords,
+                      extractor: NgramExtractor,
+                      feature: Feature,
+                      classes: Vec<String>)
+                      -> Self {
+        let mut class_sizes = class_sizes(labels);
+        class_sizes.resize(classes.len(), 0);
+        Vectorizer {
+            stopwords,
+            extractor,
+            feature,
+            norm: Norm::L2,
+            doc_count: stats.doc_count,
+            avg_doc_len: mean(&stats.doc_lens),
+            classes,
+            class_sizes,
+            term2dim: term2dim(&stats.terms),
+            terms: stats.terms,
+            doc_freqs: stats.doc_freqs,
+            class_dfs: stats.class_dfs,
+            embedder: None,
+        }
+    }
+
+    /// sets how the features are scaled.
+    pub fn with_norm(mut self, norm: Norm) -> Self {
+        self.norm = norm;
+        self
+    }
+
+    /// appends the document vectors from `embedder` after the dimensions of
+    /// the terms.
+    pub fn with_embedder(mut self, embedder: DocEmbedder) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    pub fn stopwords(&self) -> &Stopwords {
+        &self.stopwords
+    }
+
+    pub fn extractor(&self) -> &NgramExtractor {
+        &self.extractor
+    }
+
+    pub fn feature(&self) -> Feature {
+        self.feature
+    }
+
+    pub fn norm(&self) -> Norm {
+        self.norm
+    }
+
+    pub fn doc_count(&self) -> usize {
+        self.doc_count
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// the hashed terms, in the order of the dimensions they are mapped to.
+    pub fn terms(&self) -> &[usize] {
+        &self.terms
+    }
+
+    pub fn embedder(&self) -> Option<&DocEmbedder> {
+        self.embedder.as_ref()
+    }
+
+    /// the total number of output dimensions.
+    pub fn dim(&self) -> usize {
+        self.terms.len() + self.embedder.as_ref().map_or(0, DocEmbedder::dim)
+    }
+
+    /// returns the scaled feature vector for a document of `sentences`, which
+    /// count as tagged if any token has a tag; see `document_terms`.
+    pub fn transform(&self, sentences: &[Vec<Token>]) -> SparseVec<f32> {
+        let (terms, tokens) = document_terms(&self.stopwords, &self.extractor, sentences,
+                                             has_tags(sentences), None);
+        self.transform_terms(terms, &tokens)
+    }
+
+    /// returns the scaled feature vector for a document with `(term, sign)`
+    /// pairs and `tokens`, exactly as `InvertedIndex::doc_features` followed
+    /// by `append_embedding` would have for a document in the index; unknown
+    /// terms are ignored. `tokens` are only needed with an embedder.
+    pub fn transform_terms<I, S>(&self, terms: I, tokens: &[S]) -> SparseVec<f32>
+        where I: IntoIterator<Item = (usize, isize)>,
+              S: AsRef<str>
+    {
+        let mut dim2tf = SparseVec::new();
+        for (term, sign) in terms {
+            if let Some(&dim) = self.term2dim.get(&term) {
+                dim2tf.update_mut(dim, 0, |n| *n += sign)
+            }
+        }
+        let doc_len = dim2tf.iter().map(|&(_, tf)| tf.unsigned_abs()).sum();
+        let no_class_dfs = Vec::new();
+        let mut feat_vec = dim2tf.into_iter()
+            .filter(|&(_, tf)| tf != 0)
+            .map(|(dim, tf)| {
+                let scope = TermScope {
+                    avg_doc_len: self.avg_doc_len,
+                    df: self.doc_freqs[dim],
+                    doc_count: self.doc_count,
+                    class_dfs: self.class_dfs.get(dim).unwrap_or(&no_class_dfs),
+                    class_sizes: &self.class_sizes,
+                };
+                (dim, scope.signed_weight(&self.feature, tf, doc_len))
+            })
+            .collect();
+        self.norm.apply(&mut feat_vec);
+        self.append_embedding(feat_vec, tokens)
+    }
+
+    /// appends the unit document vector of `tokens` to the scaled vector
+    /// `feat_vec` of the terms, and scales the result again by the norm; with
+    /// `L2`, both parts weigh the same. without an embedder, returns
+    /// `feat_vec`.
+    pub fn append_embedding<S>(&self, feat_vec: SparseVec<f32>, tokens: &[S]) -> SparseVec<f32>
+        where S: AsRef<str>
+    {
+        let embedder = match self.embedder {
+            Some(ref embedder) => embedder,
+            None => return feat_vec,
+        };
+        let offset = self.terms.len();
+        let mut feat_vec = feat_vec.into_iter()
+            .chain(embedder.embed(tokens)
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, x)| x != 0.0)
+                .map(|(i, x)| (offset + i, x)))
+            .collect();
+        self.norm.apply(&mut feat_vec);
+        feat_vec
+    }
+
+    pub fn save<W>(&self, wtr: &mut W) -> io::Result<()>
+        where W: Write
+    {
+        let hasher = self.extractor.hasher();
+        writeln!(wtr, "stopwords {}", self.stopwords.name())?;
+        if let Stopwords::Lists(ref filter) = self.stopwords {
+            for (key, list) in [("tags", filter.tags()), ("lemmas", filter.lemmas())] {
+                writeln!(wtr, "{} {}", key, list.len())?;
+                for entry in list {
+                    writeln!(wtr, "{}", entry)?;
+                }
+            }
+        }
+        writeln!(wtr, "unit {}", self.extractor.unit().name())?;
+        writeln!(wtr, "ngrams {}", format_specs(self.extractor.specs()))?;
+        writeln!(wtr, "clusters {}", self.extractor.clusters_path().unwrap_or("none"))?;
+        writeln!(wtr, "hash {}", hasher.algorithm().name())?;
+        writeln!(wtr, "seed {}", hasher.seed())?;
+        writeln!(wtr, "signed {}", hasher.signed())?;
+        writeln!(wtr, "feature {}", self.feature.name())?;
+        writeln!(wtr, "norm {}", self.norm.name())?;
+        writeln!(wtr, "doc_count {}", self.doc_count)?;
+        writeln!(wtr, "avg_doc_len {}", self.avg_doc_len)?;
+        writeln!(wtr, "classes {}", self.classes.len())?;
+        HashMapNumberer::from_strings(&self.classes).save(wtr)?;
+        let class_sizes = self.class_sizes.iter().map(ToString::to_string).collect::<Vec<_>>();
+        writeln!(wtr, "class_sizes {}", class_sizes.join(" "))?;
+        writeln!(wtr, "dims {}", self.terms.len())?;
+        for (dim, (term, df)) in self.terms.iter().zip(self.doc_freqs.iter()).enumerate() {
+            match self.class_dfs.get(dim) {
+                None => writeln!(wtr, "{}\t{}", term, df)?,
+                Some(class_dfs) => {
+                    let class_dfs = class_dfs.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    writeln!(wtr, "{}\t{}\t{}", term, df, class_dfs.join(" "))?
+                }
+            }
+        }
+        match self.embedder {
+            None => writeln!(wtr, "embeddings none")?,
+            Some(ref embedder) => {
+                writeln!(wtr, "embeddings {}", embedder.pooling().name())?;
+                writeln!(wtr, "path {}", embedder.path())?;
+                writeln!(wtr, "doc_count {}", embedder.doc_count())?;
+                let mut word_dfs = embedder.word_dfs().iter().collect::<Vec<_>>();
+                word_dfs.sort();
+                writeln!(wtr, "words {}", word_dfs.len())?;
+                for (word, df) in word_dfs {
+                    writeln!(wtr, "{}\t{}", word, df)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load<R>(rdr: R) -> Result<Vectorizer, LoadError>
+        where R: BufRead
+    {
+        let mut lines = rdr.lines();
+        let mut next_line = || -> Result<String, LoadError> {
+            match lines.next() {
+                Some(res_line) => Ok(res_line?),
+                None => Err(FormatError::new("unexpected end of file").into()),
+            }
+        };
+        let line = next_line()?;
+        let stopwords = match field(&line, "stopwords")? {
+            "lists" => {
+                let mut tags = Vec::new();
+                for _ in 0..parse_field(&next_line()?, "tags")? {
+                    tags.push(next_line()?)
+                }
+                let mut lemmas = Vec::new();
+                for _ in 0..parse_field(&next_line()?, "lemmas")? {
+                    lemmas.push(next_line()?)
+                }
+                Stopwords::Lists(ListStopwordFilter::new(tags, lemmas))
+            }
+            name => Stopwords::from_name(name).ok_or_else(|| FormatError::new(&line))?,
+        };
+        let line = next_line()?;
+        let unit = TokenUnit::from_name(field(&line, "unit")?).ok_or_else(|| FormatError::new(&line))?;
+        let specs = parse_specs(field(&next_line()?, "ngrams")?)?;
+        let clusters_path = match field(&next_line()?, "clusters")? {
+            "none" => None,
+            path => Some(path.to_owned()),
+        };
+        let line = next_line()?;
+        let algorithm = HashAlgorithm::from_name(field(&line, "hash")?)
+            .ok_or_else(|| FormatError::new(&line))?;
+        let line = next_line()?;
+        let seed = field(&line, "seed")?.parse::<u32>().map_err(|_| FormatError::new(&line))?;
+        let line = next_line()?;
+        let signed = field(&line, "signed")?.parse::<bool>().map_err(|_| FormatError::new(&line))?;
+        let mut extractor = NgramExtractor::new(unit, specs, FeatureHasher::new(algorithm, seed, signed));
+        if let Some(path) = clusters_path {
+            let clusters = read_clusters(BufReader::new(File::open(&path)?))?;
+            extractor = extractor.with_clusters(&path, Arc::new(clusters));
+        }
+        let line = next_line()?;
+        let feature = Feature::from_name(field(&line, "feature")?)
+            .ok_or_else(|| FormatError::new(&line))?;
+        let line = next_line()?;
+        let norm = Norm::from_name(field(&line, "norm")?).ok_or_else(|| FormatError::new(&line))?;
+        let doc_count = parse_field(&next_line()?, "doc_count")?;
+        let avg_doc_len = field(&next_line()?, "avg_doc_len")?.parse()?;
+        let mut classes = HashMapNumberer::new();
+        for _ in 0..parse_field(&next_line()?, "classes")? {
+            classes.load_line(&next_line()?)?;
+        }
+        let line = next_line()?;
+        let class_sizes = field(&line, "class_sizes")?
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<usize>, _>>()?;
+        if class_sizes.len() != classes.len() {
+            return Err(FormatError::new(&line).into());
+        }
+        let classes = classes.strings().to_vec();
+        let dims = parse_field(&next_line()?, "dims")?;
+        let mut terms = Vec::with_capacity(dims);
+        let mut doc_freqs = Vec::with_capacity(dims);
+        let mut class_dfs = Vec::new();
+        let mut with_class_dfs = None;
+        for _ in 0..dims {
+            let line = next_line()?;
+            let fields = line.split('\t').collect::<Vec<_>>();
+            // the class dfs come with every term or with none.
+            let has_class_dfs = fields.len() == 3;
+            if !(2..4).contains(&fields.len()) ||
+               *with_class_dfs.get_or_insert(has_class_dfs) != has_class_dfs {
+                return Err(FormatError::new(&line).into());
+            }
+            terms.push(fields[0].parse()?);
+            doc_freqs.push(fields[1].parse()?);
+            if has_class_dfs {
+                let dfs = fields[2].split(' ').map(str::parse).collect::<Result<Vec<usize>, _>>()?;
+                if dfs.len() != class_sizes.len() {
+                    return Err(FormatError::new(&line).into());
+                }
+                class_dfs.push(dfs);
+            }
+        }
+        let vectorizer = Vectorizer {
+            stopwords,
+            extractor,
+            feature,
+            norm,
+            doc_count,
+            avg_doc_len,
+            classes,
+            class_sizes,
+            term2dim: term2dim(&terms),
+            terms,
+            doc_freqs,
+            class_dfs,
+            embedder: None,
+        };
+        let line = next_line()?;
+        let pooling = match field(&line, "embeddings")? {
+            "none" => return Ok(vectorizer),
+            name => Pooling::from_name(name).ok_or_else(|| FormatError::new(&line))?,
+        };
+        let path = field(&next_line()?, "path")?.to_owned();
+        let embeddings = Arc::new(read_embeddings(&path)?);
+        let doc_count = parse_field(&next_line()?, "doc_count")?;
+        let mut word_dfs = HashMap::new();
+        for _ in 0..parse_field(&next_line()?, "words")? {
+            let line = next_line()?;
+            let x = line.rfind('\t').ok_or_else(|| FormatError::new(&line))?;
+            word_dfs.insert(line[..x].to_owned(), line[x + 1..].parse()?);
+        }
+        let embedder = DocEmbedder::from_parts(&path, embeddings, pooling, doc_count, word_dfs);
+        Ok(vectorizer.with_embedder(embedder))
+    }
+}
+
+/// configures and fits a `Vectorizer` on documents of tokens, as the
+/// `featurize` mode does, without feature selection and embeddings.
+///
+/// # example
+/// ```
+/// use doc_class::vectorizer::Vectorizer;
+/// use doc_class::extraction::{Token, parse_specs};
+/// use doc_class::filters::Stopwords;
+/// use doc_class::weighting::Feature;
+/// let doc = |words: &[&str]| {
+///     vec![words.iter().map(|w| Token::new(w, w, "NN")).collect::<Vec<_>>()]
+/// };
+/// let docs = vec![doc(&["cheap", "flights"]), doc(&["cheap", "pills"]), doc(&["new", "york"])];
+/// let (vectorizer, features) = Vectorizer::builder()
+///     .with_stopwords(Stopwords::NoFilter)
+///     .with_specs(parse_specs("1:2^10").unwrap())
+///     .with_feature(Feature::Binary)
+///     .fit_transform(&docs, &["spam", "spam", "ham"]);
+/// assert_eq!(vectorizer.classes(), ["spam", "ham"]);
+/// assert_eq!(vectorizer.transform(&docs[0]), features[0]);
+/// ```
+#[derive(Debug,Clone,PartialEq)]
+pub struct VectorizerBuilder {
+    stopwords: Stopwords,
+    unit: TokenUnit,
+    specs: Vec<NgramSpec>,
+    hasher: FeatureHasher,
+    clusters: Option<(String, Arc<HashMap<String, usize>>)>,
+    feature: Feature,
+    norm: Norm,
+    min_freq: usize,
+}
+
+impl Default for VectorizerBuilder {
+    /// the defaults of the `featurize` mode.
+    fn default() -> Self {
+        VectorizerBuilder {
+            stopwords: Stopwords::Ptb,
+            unit: TokenUnit::Form,
+            specs: parse_specs("1:2^24").unwrap(),
+            hasher: FeatureHasher::default(),
+            clusters: None,
+            feature: Feature::STfIdf,
+            norm: Norm::L2,
+            min_freq: 1,
+        }
+    }
+}
+
+impl Vectorizer {
+    pub fn builder() -> VectorizerBuilder {
+        VectorizerBuilder::default()
+    }
+}
+
+impl VectorizerBuilder {
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    pub fn with_unit(mut self, unit: TokenUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// sets the blocks of ngrams, as from `parse_specs`.
+    pub fn with_specs(mut self, specs: Vec<NgramSpec>) -> Self {
+        self.specs = specs;
+        self
+    }
+
+    pub fn with_hasher(mut self, hasher: FeatureHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// sets the word clusters for `Cluster` ngrams, as
+    /// `NgramExtractor::with_clusters`.
+    pub fn with_clusters(mut self, path: &str, clusters: Arc<HashMap<String, usize>>) -> Self {
+        self.clusters = Some((path.to_owned(), clusters));
+        self
+    }
+
+    pub fn with_feature(mut self, feature: Feature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    pub fn with_norm(mut self, norm: Norm) -> Self {
+        self.norm = norm;
+        self
+    }
+
+    /// ignores the terms in fewer than `min_freq` documents.
+    pub fn with_min_freq(mut self, min_freq: usize) -> Self {
+        self.min_freq = min_freq;
+        self
+    }
+
+    pub fn extractor(&self) -> NgramExtractor {
+        let extractor = NgramExtractor::new(self.unit, self.specs.clone(), self.hasher);
+        match self.clusters {
+            Some((ref path, ref clusters)) => extractor.with_clusters(path, clusters.clone()),
+            None => extractor,
+        }
+    }
+
+    /// fits a vectorizer on `docs` of sentences, with the `classes` of the
+    /// documents numbered in the order of their first appearance.
+    pub fn fit<S>(self, docs: &[Vec<Vec<Token>>], classes: &[S]) -> Vectorizer
+        where S: AsRef<str>
+    {
+        self.fit_terms(docs, classes).0
+    }
+
+    /// as `fit`, also returning the feature vectors of `docs`.
+    pub fn fit_transform<S>(self, docs: &[Vec<Vec<Token>>], classes: &[S])
+                            -> (Vectorizer, Vec<SparseVec<f32>>)
+        where S: AsRef<str>
+    {
+        let (vectorizer, doc_terms) = self.fit_terms(docs, classes);
+        let features = doc_terms.into_iter()
+            .map(|(terms, tokens)| vectorizer.transform_terms(terms, &tokens))
+            .collect();
+        (vectorizer, features)
+    }
+
+    /// returns the fitted vectorizer with the terms and tokens of each
+    /// document.
+    fn fit_terms<S>(self, docs: &[Vec<Vec<Token>>], classes: &[S])
+                    -> (Vectorizer, Vec<DocTerms>)
+        where S: AsRef<str>
+    {
+        assert_eq!(docs.len(), classes.len(), "a class for each document");
+        let extractor = self.extractor();
+        let mut numberer = HashMapNumberer::new();
+        let labels = classes.iter().map(|class| numberer.number(class.as_ref())).collect::<Vec<_>>();
+        let mut inv_idx = InvertedIndex::new();
+        let doc_terms = docs.iter()
+            .map(|sentences| {
+                let (terms, tokens) = document_terms(&self.stopwords, &extractor, sentences,
+                                                     has_tags(sentences), None);
+                inv_idx.inv_push_signed(terms.iter().cloned());
+                (terms, tokens)
+            })
+            .collect();
+        let vectorizer = Vectorizer::fit(&inv_idx, &labels, self.stopwords, extractor,
+                                         self.feature, numberer.strings().to_vec(), self.min_freq)
+            .with_norm(self.norm);
+        (vectorizer, doc_terms)
+    }
+}
+
+/// whether any token of `sentences` has a tag.
+fn has_tags(sentences: &[Vec<Token>]) -> bool {
+    sentences.iter().flatten().any(|tok| tok.pos.is_some())
+}
+
+/// the `(term, sign)` pairs of a document, with its tokens as units.
+type DocTerms = (Vec<(usize, isize)>, Vec<String>);
+
+/// returns the hashed ngrams of a document of `sentences` as `(term, sign)`
+/// pairs, together with its tokens as units of `extractor`, without the
+/// stopwords. in `tagged` sentences, tokens without a tag or a lemma are
+/// dropped as well; in untagged ones, a token takes its form as lemma and `_`
+/// as tag, and only the lemma lists of `filter` apply to it. the names of the
+/// ngrams are collected into `names` if given.
+pub fn document_terms(filter: &Stopwords,
+                      extractor: &NgramExtractor,
+                      sentences: &[Vec<Token>],
+                      tagged: bool,
+                      names: Option<&mut TermNames>)
+                      -> DocTerms {
+    let unit = extractor.unit();
+    let syntactic = extractor.specs().iter().any(|spec| spec.kind.is_syntactic());
+    let mut tokens = Vec::new();
+    let mut dep_sentences = Vec::new();
+    for sentence in sentences {
+        let mut dep_tokens = Vec::new();
+        for tok in sentence {
+            match tok.form {
+                Some(ref form) => {
+                    // untagged tokens stand for their own lemmas.
+                    let lemma = tok.lemma.as_ref().unwrap_or(form);
+                    let stopword = if tagged {
+                        match (tok.pos.as_ref(), tok.lemma.as_ref()) {
+                            (Some(pos), Some(lemma)) => filter.is_stopword(pos, lemma),
+                            _ => true,
+                        }
+                    } else {
+                        filter.is_untagged_stopword(lemma)
+                    };
+                    if !stopword {
+                        let pos = tok.pos.as_ref().map_or("_", String::as_str);
+                        tokens.push(unit.of(form, lemma, pos))
+                    }
+                    if syntactic {
+                        dep_tokens.push(DepToken {
+                            lemma: lemma.to_owned(),
+                            head: tok.head,
+                            rel: tok.rel.clone().unwrap_or("_".to_owned()),
+                            stopword,
+                        })
+                    }
+                }
+                None => {
+                    // keeps the positions aligned with the heads.
+                    if syntactic {
+                        dep_tokens.push(DepToken {
+                            lemma: String::new(),
+                            head: None,
+                            rel: String::new(),
+                            stopword: true,
+                        })
+                    }
+                }
+            }
+        }
+        if syntactic {
+            dep_sentences.push(dep_tokens)
+        }
+    }
+    let terms = match names {
+        Some(names) => {
+            let mut named = extractor.extract_named(&tokens);
+            named.extend(extractor.extract_deps_named(&dep_sentences));
+            named.into_iter()
+                .map(|(term, sign, name)| {
+                    names.entry(term).or_default().insert(name);
+                    (term, sign)
+                })
+                .collect()
+        }
+        None => {
+            let mut terms = extractor.extract(&tokens);
+            terms.extend(extractor.extract_deps(&dep_sentences));
+            terms
+        }
+    };
+    (terms, tokens)
+}
+
+fn term2dim(terms: &[usize]) -> HashMap<usize, usize> {
+    terms.iter().enumerate().map(|(dim, &term)| (term, dim)).collect()
+}
+
+/// returns `value` from `line` as `key value`.
+fn field<'a>(line: &'a str, key: &str) -> Result<&'a str, FormatError> {
+    let mut fields = line.splitn(2, ' ');
+    match (fields.next(), fields.next()) {
+        (Some(k), Some(value)) if k == key => Ok(value),
+        _ => Err(FormatError::new(line)),
+    }
+}
+
+fn parse_field(line: &str, key: &str) -> Result<usize, LoadError> {
+    Ok(field(line, key)?.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust2vec::{Embeddings, ReadText, WriteWord2Vec};
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::process;
+
+    fn token(form: &str, lemma: Option<&str>, pos: Option<&str>) -> Token {
+        Token {
+            form: Some(form.to_owned()),
+            lemma: lemma.map(str::to_owned),
+            pos: pos.map(str::to_owned),
+            ..Token::default()
+        }
+    }
+
+    fn doc(words: &[&str]) -> Vec<Vec<Token>> {
+        vec![words.iter().map(|w| Token::new(w, w, "NN")).collect()]
+    }
+
+    /// a vectorizer with class dfs, fitted on three documents.
+    fn fit() -> (Vec<Vec<Vec<Token>>>, Vectorizer) {
+        let docs = vec![doc(&["cheap", "flights"]), doc(&["cheap", "pills"]), doc(&["new", "york"])];
+        let vectorizer = Vectorizer::builder()
+            .with_stopwords(Stopwords::NoFilter)
+            .with_specs(parse_specs("1:2^10").unwrap())
+            .with_feature(Feature::DeltaTfIdf)
+            .fit(&docs, &["spam", "spam", "ham"]);
+        (docs, vectorizer)
+    }
+
+    fn saved() -> String {
+        let (_, vectorizer) = fit();
+        let mut buf = Vec::new();
+        vectorizer.save(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let (mut docs, vectorizer) = fit();
+        let path = env::temp_dir().join(format!("doc-class-test-{}.w2v", process::id()));
+        let text = "cheap 1 0 0\nflights 0 2 0\nnew 0 1 1\n";
+        let embeddings = Embeddings::read_text(&mut Cursor::new(text)).unwrap();
+        embeddings.write_word2vec_binary(&mut File::create(&path).unwrap()).unwrap();
+        let path = path.to_str().unwrap();
+        let tokens = docs.iter()
+            .map(|doc| doc.iter().flatten().map(|tok| tok.form.clone().unwrap()).collect::<Vec<_>>());
+        let embedder = DocEmbedder::fit(path, Arc::new(read_embeddings(path).unwrap()),
+                                        Pooling::TfIdfMean, tokens);
+        let vectorizer = vectorizer.with_embedder(embedder);
+        let mut buf = Vec::new();
+        vectorizer.save(&mut buf).unwrap();
+        let loaded = Vectorizer::load(&buf[..]).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded, vectorizer);
+        assert_eq!(loaded.class_dfs.len(), loaded.terms().len());
+        assert_eq!(loaded.dim(), loaded.terms().len() + 3);
+        docs.push(doc(&["cheap", "new", "flights", "pills", "unseen"]));
+        for doc in &docs {
+            let features = loaded.transform(doc);
+            assert_eq!(features, vectorizer.transform(doc));
+            assert!(features.iter().any(|&(dim, _)| dim >= loaded.terms().len()));
+        }
+    }
+
+    #[test]
+    fn load_rejects_a_bad_seed() {
+        let saved = saved();
+        assert!(Vectorizer::load(saved.as_bytes()).is_ok());
+        for seed in &["seed 4294967296", "seed -1", "seed x"] {
+            let bad = saved.replace("seed 0", seed);
+            assert!(Vectorizer::load(bad.as_bytes()).is_err(), "{}", seed);
+        }
+    }
+
+    #[test]
+    fn load_needs_class_dfs_for_all_terms_or_none() {
+        let saved = saved();
+        let lines = saved.lines()
+            .skip_while(|line| !line.starts_with("dims "))
+            .skip(1)
+            .take_while(|line| !line.starts_with("embeddings "))
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|line| line.split('\t').count() == 3));
+        let without = |line: &str| line.rsplit_once('\t').unwrap().0.to_owned();
+        let mixed = saved.replacen(lines[1], &without(lines[1]), 1);
+        assert!(Vectorizer::load(mixed.as_bytes()).is_err());
+        let longer = saved.replacen(lines[0], &format!("{} 1", lines[0]), 1);
+        assert!(Vectorizer::load(longer.as_bytes()).is_err());
+        let none = lines.iter().fold(saved.clone(), |none, line| none.replacen(line, &without(line), 1));
+        assert!(Vectorizer::load(none.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn tagged_tokens_need_a_tag_and_a_lemma() {
+        let extractor = Vectorizer::builder().with_specs(parse_specs("1:2^10,2:2^10").unwrap()).extractor();
+        let sentences = vec![vec![token("cats", Some("cat"), Some("NNS")),
+                                  token("purr", None, Some("VBP")),
+                                  token("loudly", Some("loudly"), None),
+                                  token("now", Some("now"), Some("RB"))]];
+        let (terms, tokens) = document_terms(&Stopwords::NoFilter, &extractor, &sentences, true, None);
+        assert_eq!(tokens, ["cats", "now"]);
+        let kept = vec![vec![sentences[0][0].clone(), sentences[0][3].clone()]];
+        assert_eq!(terms, document_terms(&Stopwords::NoFilter, &extractor, &kept, true, None).0);
+        let untagged = vec![["cats", "purr", "loudly"].iter().map(|form| Token::untagged(form)).collect()];
+        let (_, tokens) = document_terms(&Stopwords::NoFilter, &extractor, &untagged, false, None);
+        assert_eq!(tokens, ["cats", "purr", "loudly"]);
+        let stopwords = Stopwords::Lists(ListStopwordFilter::new(Vec::new(), vec!["purr".to_owned()]));
+        let (_, tokens) = document_terms(&stopwords, &extractor, &untagged, false, None);
+        assert_eq!(tokens, ["cats", "loudly"]);
+    }
+}
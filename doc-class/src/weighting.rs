@@ -0,0 +1,424 @@
+// Author: Kuan Yu, 3913893
+// Honor Code:  I pledge that this program represents my own work.
+
+use sparse_vec::{SparseVec, scale_to_unit, scale_by_l1};
+use inverted_index::{binary_tf, sublinear_tf, idf};
+
+/// everything known about a term in a document, for weighting it.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct TermContext<'a> {
+    /// the absolute term frequency in the document.
+    pub tf: usize,
+    /// the sum of the absolute term frequencies in the document.
+    pub doc_len: usize,
+    /// the mean `doc_len` over all documents.
+    pub avg_doc_len: f32,
+    pub df: usize,
+    pub doc_count: usize,
+    /// the document frequency of the term in each class; empty unless the
+    /// weighting `needs_classes`.
+    pub class_dfs: &'a [usize],
+    /// the number of documents in each class.
+    pub class_sizes: &'a [usize],
+}
+
+pub trait Weighting {
+    fn weight(&self, ctx: &TermContext) -> f32;
+
+    /// whether `weight` uses the statistics of the classes.
+    fn needs_classes(&self) -> bool {
+        false
+    }
+}
+
+/// `1` for present terms.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Binary;
+
+impl Weighting for Binary {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        binary_tf(ctx.tf)
+    }
+}
+
+/// the raw tf times `ln(N / df)`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct TfIdf;
+
+impl Weighting for TfIdf {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        ctx.tf as f32 * idf(ctx.doc_count, ctx.df)
+    }
+}
+
+/// the binary tf times `ln(N / df)`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct BTfIdf;
+
+impl Weighting for BTfIdf {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        binary_tf(ctx.tf) * idf(ctx.doc_count, ctx.df)
+    }
+}
+
+/// the sublinear tf `1 + ln(tf)` times `ln(N / df)`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct STfIdf;
+
+impl Weighting for STfIdf {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        sublinear_tf(ctx.tf) * idf(ctx.doc_count, ctx.df)
+    }
+}
+
+/// okapi bm25: the tf saturates with `k1`, and is normalized by the document
+/// length relative to the average to the extent of `b`. the idf is
+/// `ln(1 + (N - df + 0.5) / (df + 0.5))`, which is never negative.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Bm25 {
+    pub k1: f32,
+    pub b: f32,
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Bm25 { k1: 1.2, b: 0.75 }
+    }
+}
+
+impl Weighting for Bm25 {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        let tf = ctx.tf as f32;
+        let norm = 1.0 - self.b + self.b * length_ratio(ctx);
+        let idf = f32::ln(1.0 + (ctx.doc_count as f32 - ctx.df as f32 + 0.5) / (ctx.df as f32 + 0.5));
+        tf * (self.k1 + 1.0) / (tf + self.k1 * norm) * idf
+    }
+}
+
+/// pivoted length normalization after singhal et al. (1996): the doubly
+/// logarithmic tf `1 + ln(1 + ln(tf))`, divided by `1 - s + s * len / avg`
+/// with the `slope` as `s`, times `ln((N + 1) / df)`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Pivoted {
+    pub slope: f32,
+}
+
+impl Default for Pivoted {
+    fn default() -> Self {
+        Pivoted { slope: 0.2 }
+    }
+}
+
+impl Weighting for Pivoted {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        if ctx.tf == 0 {
+            return 0.0;
+        }
+        let tf = 1.0 + f32::ln(1.0 + f32::ln(ctx.tf as f32));
+        let norm = 1.0 - self.slope + self.slope * length_ratio(ctx);
+        tf / norm * f32::ln((ctx.doc_count + 1) as f32 / ctx.df as f32)
+    }
+}
+
+/// the sublinear tf times the probabilistic idf `ln((N - df) / df)`, which
+/// is cut off at `0` for terms in more than half of the documents.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct ProbIdf;
+
+impl Weighting for ProbIdf {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        let rest = ctx.doc_count.saturating_sub(ctx.df);
+        if rest <= ctx.df {
+            return 0.0;
+        }
+        sublinear_tf(ctx.tf) * f32::ln(rest as f32 / ctx.df as f32)
+    }
+}
+
+/// delta tf-idf after martineau & finin (2009), for any number of classes:
+/// the raw tf times the largest absolute difference between the smoothed idf
+/// in a class and outside it. taking the absolute value drops the sign of
+/// their score, which tells the class a term leans to; a term rare in one
+/// class weighs as much as one frequent in it.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DeltaTfIdf;
+
+impl Weighting for DeltaTfIdf {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        let n = ctx.class_sizes.iter().sum::<usize>() as f32;
+        let mut delta = 0.0f32;
+        for (&df_c, &n_c) in ctx.class_dfs.iter().zip(ctx.class_sizes) {
+            let (df_c, n_c) = (df_c as f32, n_c as f32);
+            let df_rest = ctx.df as f32 - df_c;
+            let idf_c = f32::ln((n_c + 1.0) / (df_c + 0.5));
+            let idf_rest = f32::ln((n - n_c + 1.0) / (df_rest + 0.5));
+            delta = delta.max((idf_rest - idf_c).abs());
+        }
+        ctx.tf as f32 * delta
+    }
+
+    fn needs_classes(&self) -> bool {
+        true
+    }
+}
+
+fn length_ratio(ctx: &TermContext) -> f32 {
+    if ctx.avg_doc_len > 0.0 {
+        ctx.doc_len as f32 / ctx.avg_doc_len
+    } else {
+        1.0
+    }
+}
+
+/// the weightings above by name, where bm25 and pivoted take their parameters
+/// after colons, like `bm25:1.2:0.75` and `pivoted:0.2`.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Feature {
+    Binary,
+    TfIdf,
+    BTfIdf,
+    STfIdf,
+    Bm25(Bm25),
+    Pivoted(Pivoted),
+    ProbIdf,
+    DeltaTfIdf,
+}
+
+impl Feature {
+    pub fn name(&self) -> String {
+        match *self {
+            Feature::Binary => "binary".to_owned(),
+            Feature::TfIdf => "tfidf".to_owned(),
+            Feature::BTfIdf => "btfidf".to_owned(),
+            Feature::STfIdf => "stfidf".to_owned(),
+            Feature::Bm25(Bm25 { k1, b }) => format!("bm25:{}:{}", k1, b),
+            Feature::Pivoted(Pivoted { slope }) => format!("pivoted:{}", slope),
+            Feature::ProbIdf => "probidf".to_owned(),
+            Feature::DeltaTfIdf => "deltatfidf".to_owned(),
+        }
+    }
+
+    /// # example
+    /// ```
+    /// use doc_class::weighting::{Feature, Bm25};
+    /// assert_eq!(Feature::from_name("bm25"), Some(Feature::Bm25(Bm25::default())));
+    /// assert_eq!(Feature::from_name("bm25:2:0.5"), Some(Feature::Bm25(Bm25 { k1: 2.0, b: 0.5 })));
+    /// assert_eq!(Feature::from_name("pivoted:0.3").unwrap().name(), "pivoted:0.3");
+    /// assert_eq!(Feature::from_name("tfidf:1"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Feature> {
+        let mut fields = name.split(':');
+        let feature = match fields.next()? {
+            "binary" => Feature::Binary,
+            "tfidf" => Feature::TfIdf,
+            "btfidf" => Feature::BTfIdf,
+            "stfidf" => Feature::STfIdf,
+            "probidf" => Feature::ProbIdf,
+            "deltatfidf" => Feature::DeltaTfIdf,
+            "bm25" => {
+                let mut bm25 = Bm25::default();
+                if let Some(k1) = fields.next() {
+                    bm25.k1 = k1.parse().ok()?;
+                }
+                if let Some(b) = fields.next() {
+                    bm25.b = b.parse().ok()?;
+                }
+                Feature::Bm25(bm25)
+            }
+            "pivoted" => {
+                let mut pivoted = Pivoted::default();
+                if let Some(slope) = fields.next() {
+                    pivoted.slope = slope.parse().ok()?;
+                }
+                Feature::Pivoted(pivoted)
+            }
+            _ => return None,
+        };
+        match fields.next() {
+            None => Some(feature),
+            Some(_) => None,
+        }
+    }
+}
+
+impl Weighting for Feature {
+    fn weight(&self, ctx: &TermContext) -> f32 {
+        match *self {
+            Feature::Binary => Binary.weight(ctx),
+            Feature::TfIdf => TfIdf.weight(ctx),
+            Feature::BTfIdf => BTfIdf.weight(ctx),
+            Feature::STfIdf => STfIdf.weight(ctx),
+            Feature::Bm25(ref bm25) => bm25.weight(ctx),
+            Feature::Pivoted(ref pivoted) => pivoted.weight(ctx),
+            Feature::ProbIdf => ProbIdf.weight(ctx),
+            Feature::DeltaTfIdf => DeltaTfIdf.weight(ctx),
+        }
+    }
+
+    fn needs_classes(&self) -> bool {
+        matches!(*self, Feature::DeltaTfIdf)
+    }
+}
+
+/// what is known about a term over all documents, which makes a
+/// `TermContext` with the frequency of the term in each document.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct TermScope<'a> {
+    pub avg_doc_len: f32,
+    pub df: usize,
+    pub doc_count: usize,
+    pub class_dfs: &'a [usize],
+    pub class_sizes: &'a [usize],
+}
+
+impl<'a> TermScope<'a> {
+    /// the context of the term with the absolute value of the signed `tf` in
+    /// a document of `doc_len`.
+    pub fn context(&self, tf: isize, doc_len: usize) -> TermContext<'a> {
+        TermContext {
+            tf: tf.unsigned_abs(),
+            doc_len,
+            avg_doc_len: self.avg_doc_len,
+            df: self.df,
+            doc_count: self.doc_count,
+            class_dfs: self.class_dfs,
+            class_sizes: self.class_sizes,
+        }
+    }
+
+    /// applies `weighting` to the context of the signed `tf` in a document of
+    /// `doc_len`, and gives the result the sign of `tf`.
+    pub fn signed_weight<W>(&self, weighting: &W, tf: isize, doc_len: usize) -> f32
+        where W: Weighting + ?Sized
+    {
+        let feat = weighting.weight(&self.context(tf, doc_len));
+        if tf < 0 { -feat } else { feat }
+    }
+}
+
+/// how feature vectors are scaled.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Norm {
+    /// to unit euclidean length.
+    L2,
+    /// to unit sum of absolute values.
+    L1,
+    NoNorm,
+}
+
+impl Norm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Norm::L2 => "l2",
+            Norm::L1 => "l1",
+            Norm::NoNorm => "none",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Norm> {
+        match name {
+            "l2" => Some(Norm::L2),
+            "l1" => Some(Norm::L1),
+            "none" => Some(Norm::NoNorm),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, v: &mut SparseVec<f32>) {
+        match *self {
+            Norm::L2 => scale_to_unit(v),
+            Norm::L1 => scale_by_l1(v),
+            Norm::NoNorm => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(df: usize, doc_count: usize, avg_doc_len: f32) -> TermScope<'static> {
+        TermScope {
+            avg_doc_len,
+            df,
+            doc_count,
+            class_dfs: &[],
+            class_sizes: &[],
+        }
+    }
+
+    fn assert_close(x: f32, y: f32) {
+        assert!((x - y).abs() < 1e-5, "{} != {}", x, y);
+    }
+
+    #[test]
+    fn bm25_saturates_and_normalizes_the_length() {
+        let bm25 = Bm25::default();
+        let scope = scope(2, 10, 10.0);
+        let idf = f32::ln(1.0 + 8.5 / 2.5);
+        assert_close(bm25.weight(&scope.context(1, 10)), idf);
+        assert_close(bm25.weight(&scope.context(3, 10)), 3.0 * 2.2 / 4.2 * idf);
+        // each occurrence adds less, towards the bound `(k1 + 1) * idf`.
+        let weights = (1..50).map(|tf| bm25.weight(&scope.context(tf, 10))).collect::<Vec<_>>();
+        for w in weights.windows(3) {
+            assert!(w[0] < w[1] && w[1] - w[0] > w[2] - w[1]);
+        }
+        assert!(weights[48] < 2.2 * idf);
+        // twice the average length: `1 - b + 2b = 1.75`.
+        assert_close(bm25.weight(&scope.context(1, 20)), 2.2 / (1.0 + 1.2 * 1.75) * idf);
+        let flat = Bm25 { k1: 1.2, b: 0.0 };
+        assert_eq!(flat.weight(&scope.context(1, 20)), flat.weight(&scope.context(1, 10)));
+    }
+
+    #[test]
+    fn length_ratio_is_one_without_an_average() {
+        let ctx = scope(2, 10, 0.0).context(1, 7);
+        assert_eq!(length_ratio(&ctx), 1.0);
+        assert_eq!(length_ratio(&scope(2, 10, 3.5).context(1, 7)), 2.0);
+        assert_close(Bm25::default().weight(&ctx), f32::ln(1.0 + 8.5 / 2.5));
+        assert_close(Pivoted::default().weight(&ctx), f32::ln(11.0 / 2.0));
+    }
+
+    #[test]
+    fn pivoted_divides_by_the_pivoted_length() {
+        let pivoted = Pivoted::default();
+        let scope = scope(2, 10, 10.0);
+        let idf = f32::ln(11.0 / 2.0);
+        assert_close(pivoted.weight(&scope.context(1, 10)), idf);
+        assert_close(pivoted.weight(&scope.context(1, 20)), idf / 1.2);
+        assert_close(pivoted.weight(&scope.context(3, 10)), (1.0 + f32::ln(1.0 + f32::ln(3.0))) * idf);
+        assert_eq!(pivoted.weight(&scope.context(0, 10)), 0.0);
+    }
+
+    #[test]
+    fn prob_idf_is_cut_off_at_half_of_the_documents() {
+        assert_close(ProbIdf.weight(&scope(4, 10, 1.0).context(1, 1)), f32::ln(6.0 / 4.0));
+        assert_close(ProbIdf.weight(&scope(4, 10, 1.0).context(3, 1)),
+                     (1.0 + f32::ln(3.0)) * f32::ln(6.0 / 4.0));
+        assert_eq!(ProbIdf.weight(&scope(5, 10, 1.0).context(3, 1)), 0.0);
+        assert_eq!(ProbIdf.weight(&scope(6, 10, 1.0).context(3, 1)), 0.0);
+        assert_eq!(ProbIdf.weight(&scope(10, 10, 1.0).context(3, 1)), 0.0);
+    }
+
+    #[test]
+    fn delta_tf_idf_takes_the_largest_difference_over_classes() {
+        let scope = TermScope {
+            avg_doc_len: 1.0,
+            df: 4,
+            doc_count: 10,
+            class_dfs: &[3, 0, 1],
+            class_sizes: &[4, 3, 3],
+        };
+        let deltas = [f32::ln(7.0 / 1.5) - f32::ln(5.0 / 3.5),
+                      f32::ln(8.0 / 4.5) - f32::ln(4.0 / 0.5),
+                      f32::ln(8.0 / 3.5) - f32::ln(4.0 / 1.5)];
+        // the term is absent from the second class, which gives the largest
+        // difference, though a negative one.
+        assert!(deltas[1] < 0.0 && deltas[1].abs() > deltas[0] && deltas[0] > deltas[2].abs());
+        assert!(DeltaTfIdf.needs_classes());
+        assert_close(DeltaTfIdf.weight(&scope.context(2, 1)), 2.0 * deltas[1].abs());
+        assert_close(scope.signed_weight(&DeltaTfIdf, -2, 1), -2.0 * deltas[1].abs());
+        let even = TermScope { class_dfs: &[2, 1, 1], class_sizes: &[4, 2, 2], ..scope };
+        assert_close(DeltaTfIdf.weight(&even.context(1, 1)), 0.0);
+    }
+}